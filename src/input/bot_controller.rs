@@ -0,0 +1,291 @@
+use std::{cmp::Ordering, f32::consts::PI, fmt, sync::Mutex};
+
+use rapier2d::{math::Point, na::Vector2};
+
+use crate::scene::maze::{Cell, Maze};
+
+use super::{Controller, TankPose, WorldView};
+
+/// How good a bot plays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+/// A controller driven by the program, it chases the nearest opponent through the maze
+/// and fires when the opponent is in sight.
+pub struct Bot {
+    difficulty: Difficulty,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    ticks: u32,
+    /// Where the bot is heading to, re-planned every `replan_interval` ticks.
+    waypoint: Option<Point<f32>>,
+    /// Whether an opponent is in sight at the waypoint.
+    aiming: bool,
+    last_fire: Option<u32>,
+    movement: (f32, f32),
+    fire: bool,
+}
+
+/// Behaviours differing between difficulties.
+struct Skill {
+    /// Ticks between two decisions.
+    replan_interval: u32,
+    /// Fire only if the barrel is this close to the target, in radians.
+    aim_tolerance: f32,
+    /// Ticks between two shots.
+    fire_interval: u32,
+    /// Max rotation control, lower means turning slower.
+    turn_speed: f32,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Bot {
+        Bot {
+            difficulty,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn skill(&self) -> Skill {
+        match self.difficulty {
+            Difficulty::Easy => Skill {
+                replan_interval: 30,
+                aim_tolerance: 0.35,
+                fire_interval: 90,
+                turn_speed: 0.5,
+            },
+            Difficulty::Normal => Skill {
+                replan_interval: 10,
+                aim_tolerance: 0.15,
+                fire_interval: 45,
+                turn_speed: 0.8,
+            },
+            Difficulty::Hard => Skill {
+                replan_interval: 1,
+                aim_tolerance: 0.06,
+                fire_interval: 20,
+                turn_speed: 1.0,
+            },
+        }
+    }
+}
+
+impl Controller for Bot {
+    fn movement_status(&self) -> (f32, f32) {
+        self.state.lock().unwrap().movement
+    }
+
+    fn fire_status(&self) -> bool {
+        self.state.lock().unwrap().fire
+    }
+
+    fn observe(&self, world: &WorldView) {
+        let skill = self.skill();
+        let state = &mut *self.state.lock().unwrap();
+        state.ticks += 1;
+        // Fire is triggered when the button goes down, so release it after every shot.
+        state.fire = false;
+        let me = world.tanks[world.me];
+        if !me.alive {
+            state.waypoint = None;
+            state.movement = (0.0, 0.0);
+            return;
+        }
+        if state.ticks % skill.replan_interval == 0 || state.waypoint.is_none() {
+            let plan = plan(world.maze, world.tanks, world.me);
+            state.waypoint = plan.map(|(waypoint, _)| waypoint);
+            state.aiming = plan.map_or(false, |(_, aiming)| aiming);
+        }
+        let waypoint = match state.waypoint {
+            Some(waypoint) => waypoint,
+            None => {
+                state.movement = (0.0, 0.0);
+                return;
+            }
+        };
+
+        let error = angle_error(&me, &waypoint);
+        let rot = (-error * 2.0).clamp(-skill.turn_speed, skill.turn_speed);
+        let acl = if state.aiming {
+            0.0
+        } else if error.abs() < 0.5 {
+            1.0
+        } else {
+            0.2
+        };
+        state.movement = (rot, acl);
+
+        let reloaded = state
+            .last_fire
+            .map_or(true, |last| state.ticks - last >= skill.fire_interval);
+        if state.aiming && reloaded && error.abs() < skill.aim_tolerance {
+            state.fire = true;
+            state.last_fire = Some(state.ticks);
+        }
+    }
+}
+
+/// Find where to go, returns the point to face and whether it is an opponent in sight.
+fn plan(maze: &Maze, tanks: &[TankPose], me: usize) -> Option<(Point<f32>, bool)> {
+    let position = tanks[me].position;
+    let my_cell = maze.cell_at(&position)?;
+    let opponents = tanks
+        .iter()
+        .enumerate()
+        .filter(|&(i, tank)| i != me && tank.alive);
+
+    if let Some(target) = opponents
+        .clone()
+        .filter(|(_, tank)| in_sight(maze, &position, &tank.position))
+        .min_by(|(_, a), (_, b)| {
+            let a = (a.position - position).norm();
+            let b = (b.position - position).norm();
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        })
+    {
+        return Some((target.1.position, true));
+    }
+
+    // Chase the nearest opponent along the maze
+    let field = maze.distances_from(my_cell);
+    let (_, target_cell) = opponents
+        .filter_map(|(_, tank)| maze.cell_at(&tank.position))
+        .filter_map(|cell| field.get(cell).map(|distance| (distance, cell)))
+        .min()?;
+    let next: Cell = maze
+        .distances_from(target_cell)
+        .next_step(maze, my_cell)
+        .unwrap_or(target_cell);
+    Some((maze.cell_center(next), false))
+}
+
+/// Whether the straight line between two points crosses no wall.
+fn in_sight(maze: &Maze, from: &Point<f32>, to: &Point<f32>) -> bool {
+    const STEP: f32 = 0.05;
+    let delta = to - from;
+    let steps = (delta.norm() / STEP).ceil() as usize;
+    let mut cell = match maze.cell_at(from) {
+        Some(cell) => cell,
+        None => return false,
+    };
+    for i in 1..=steps {
+        let point = from + delta * (i as f32 / steps as f32);
+        let next = match maze.cell_at(&point) {
+            Some(next) => next,
+            None => return false,
+        };
+        if next != cell {
+            // Moving to a diagonal cell counts as blocked
+            if maze.wall_between(cell, next) != Some(false) {
+                return false;
+            }
+            cell = next;
+        }
+    }
+    true
+}
+
+/// The angle to turn from the tank's heading to the target, in `(-PI, PI]`,
+/// positive means counterclockwise.
+fn angle_error(tank: &TankPose, target: &Point<f32>) -> f32 {
+    let direction: Vector2<f32> = target - tank.position;
+    let target_angle = (-direction.x).atan2(direction.y);
+    let mut error = (target_angle - tank.rotation) % (2.0 * PI);
+    if error > PI {
+        error -= 2.0 * PI;
+    } else if error <= -PI {
+        error += 2.0 * PI;
+    }
+    error
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Normal => write!(f, "normal"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::maze::ARENA;
+
+    use super::*;
+
+    fn pose(maze: &Maze, cell: Cell, rotation: f32) -> TankPose {
+        TankPose {
+            position: maze.cell_center(cell),
+            rotation,
+            alive: true,
+        }
+    }
+
+    #[test]
+    fn angles() {
+        let tank = TankPose {
+            position: Point::origin(),
+            rotation: 0.0,
+            alive: true,
+        };
+        assert!(angle_error(&tank, &Point::new(0.0, 1.0)).abs() < 1e-6);
+        assert!((angle_error(&tank, &Point::new(-1.0, 0.0)) - PI / 2.0).abs() < 1e-6);
+        assert!((angle_error(&tank, &Point::new(1.0, 0.0)) + PI / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn walls_block_sight() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        let center = |cell| maze.cell_center(cell);
+        assert!(in_sight(&maze, &center((0, 1)), &center((2, 1))));
+        assert!(!in_sight(&maze, &center((0, 0)), &center((1, 0))));
+        assert!(!in_sight(&maze, &center((0, 0)), &center((2, 1))));
+    }
+
+    #[test]
+    fn fires_at_opponent_in_sight() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        // The opponent is at the right, and the bot faces it.
+        let tanks = [pose(&maze, (0, 1), -PI / 2.0), pose(&maze, (2, 1), 0.0)];
+        let bot = Bot::new(Difficulty::Hard);
+        bot.observe(&WorldView {
+            maze: &maze,
+            tanks: &tanks,
+            me: 0,
+        });
+        assert!(bot.fire_status());
+        // The button is released at the next tick.
+        bot.observe(&WorldView {
+            maze: &maze,
+            tanks: &tanks,
+            me: 0,
+        });
+        assert!(!bot.fire_status());
+    }
+
+    #[test]
+    fn chases_opponent_along_the_maze() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        // The only way from (0, 0) to (1, 0) goes up first.
+        let tanks = [pose(&maze, (0, 0), 0.0), pose(&maze, (1, 0), 0.0)];
+        assert_eq!(plan(&maze, &tanks, 0), Some((maze.cell_center((0, 1)), false)));
+        let bot = Bot::new(Difficulty::Normal);
+        bot.observe(&WorldView {
+            maze: &maze,
+            tanks: &tanks,
+            me: 0,
+        });
+        let (rot, acl) = bot.movement_status();
+        assert!(rot.abs() < 1e-6 && acl > 0.0);
+        assert!(!bot.fire_status());
+    }
+}
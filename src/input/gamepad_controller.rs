@@ -7,7 +7,15 @@ use std::cell::RefCell;
 use gilrs::{Axis, Button, Event, GamepadId};
 
 pub struct Gamepad {
-    controllers: RefCell<HashMap<GamepadId, Arc<Mutex<(f32, f32)>>>>,
+    controllers: RefCell<HashMap<GamepadId, Arc<Mutex<Status>>>>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Status {
+    movement: (f32, f32),
+    fire: bool,
+    /// Fire is pressed in this tick, even if it's released already.
+    fired: bool,
 }
 
 impl Gamepad {
@@ -18,6 +26,7 @@ impl Gamepad {
     }
     pub fn input_event(&self, gilrs: &gilrs::Gilrs, Event { id, .. }: &Event) {
         if let Some(ctrl) = self.controllers.borrow().get(id) {
+            let fired = ctrl.lock().unwrap().fired;
             *ctrl.lock().unwrap() = {
                 let gamepad = gilrs.gamepad(*id);
                 let get_axis = |axis: Axis| gamepad.axis_data(axis).map_or(0.0, |x| x.value());
@@ -45,23 +54,41 @@ impl Gamepad {
                 });
                 let rot = control.next().unwrap();
                 let acl = control.next().unwrap();
-                (rot, acl.max(-0.6))
+                let fire = [Button::South, Button::RightTrigger, Button::RightTrigger2]
+                    .iter()
+                    .any(|button| gamepad.is_pressed(*button));
+                Status {
+                    movement: (rot, acl.max(-0.6)),
+                    fire,
+                    fired: fired || fire,
+                }
             };
         }
     }
+    /// Forget presses of fire in the last tick.
+    pub fn new_tick(&self) {
+        for status in self.controllers.borrow().values() {
+            status.lock().unwrap().fired = false;
+        }
+    }
     pub fn create_gamepad_controller(&self, gamepad: GamepadId) -> Controller {
-        let status = Arc::new(Mutex::new((0.0, 0.0)));
+        let status = Arc::new(Mutex::new(Status::default()));
         self.controllers.borrow_mut().insert(gamepad, status.clone());
         Controller { status }
     }
 }
 
 pub struct Controller {
-    status: Arc<Mutex<(f32, f32)>>,
+    status: Arc<Mutex<Status>>,
 }
 
 impl super::Controller for Controller {
     fn movement_status(&self) -> (f32, f32) {
-        *self.status.lock().unwrap()
+        self.status.lock().unwrap().movement
+    }
+
+    fn fire_status(&self) -> bool {
+        let status = self.status.lock().unwrap();
+        status.fire || status.fired
     }
 }
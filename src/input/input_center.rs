@@ -1,90 +1,214 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use crossbeam_channel::{Receiver, Sender, unbounded};
 use gilrs::GamepadId;
 #[allow(unused_imports)]
 use log::{debug, error, info, log_enabled};
-use winit::event::{KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
 
 use super::{
+    bot_controller::{Bot, Difficulty},
     Controller,
     gamepad_controller::Gamepad,
-    keyboard_controller::{Key, Keyboard},
+    key_bindings::KeyBindings,
+    keyboard_controller::Keyboard,
 };
 
 pub struct InputCenter {
     gilrs: RefCell<gilrs::Gilrs>,
     gamepad_ctrl: Gamepad,
     keyboard_ctrl: Keyboard,
+    key_bindings: KeyBindings,
     keyboard_receiver: Receiver<KeyboardInput>,
+    pointer_receiver: Receiver<PointerEvent>,
+    /// The tick of the next update.
+    tick: Cell<u64>,
 }
 
 #[derive(Clone)]
 pub struct InputEventSender {
     keyboard_sender: Sender<KeyboardInput>,
+    pointer_sender: Sender<PointerEvent>,
+    frame_size: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum PointerEvent {
+    /// The cursor is moved, both the position and the frame size are in physical pixels.
+    Moved {
+        position: [f32; 2],
+        frame_size: [f32; 2],
+    },
+    Pressed(MouseButton),
+}
+
+/// An input event, stamped with the tick it applies to.
+#[derive(Clone, Copy, Debug)]
+pub struct InputEvent {
+    pub tick: u64,
+    pub kind: EventKind,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum EventKind {
+    Keyboard(KeyboardInput),
+    Gamepad(gilrs::Event),
+    Pointer(PointerEvent),
+}
+
+/// Where the inputs of a player come from, a keyboard cluster or a gamepad joins only once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// Index of the cluster in the key bindings.
+    Keyboard(usize),
+    Gamepad(GamepadId),
+    Bot(Difficulty),
+}
+
+/// All events taken by one update, events of each device are in the order they happened.
+pub struct EventBatch {
+    pub events: Vec<InputEvent>,
 }
 
 impl InputCenter {
-    pub fn new() -> (Self, InputEventSender) {
+    pub fn new(key_bindings: KeyBindings) -> (Self, InputEventSender) {
         let gilrs = gilrs::Gilrs::new().unwrap();
         let (keyboard_sender, keyboard_receiver) = unbounded();
+        let (pointer_sender, pointer_receiver) = unbounded();
         (
             InputCenter {
                 gilrs: RefCell::new(gilrs),
                 gamepad_ctrl: Gamepad::new(),
                 keyboard_ctrl: Keyboard::new(),
+                key_bindings,
                 keyboard_receiver,
+                pointer_receiver,
+                tick: Cell::new(0),
+            },
+            InputEventSender {
+                keyboard_sender,
+                pointer_sender,
+                frame_size: [1.0, 1.0],
             },
-            InputEventSender { keyboard_sender },
         )
     }
 
-    pub fn update<KH, GH, R>(
-        &self,
-        keyboard_event_handler: KH,
-        gamepad_event_handler: GH,
-    ) -> Result<Option<R>, crossbeam_channel::RecvError>
-        where
-            KH: FnOnce(&KeyboardInput) -> R,
-            GH: FnOnce(&gilrs::Gilrs, &gilrs::Event) -> R,
-    {
-        let gilrs = &mut *self.gilrs.borrow_mut();
-        if let Ok(input) = self.keyboard_receiver.try_recv() {
+    /// Take every pending event, controllers see all of them in this tick.
+    /// Scenes call it once per tick, before polling the controllers.
+    pub fn update(&self) -> EventBatch {
+        let tick = self.tick.get();
+        self.tick.set(tick + 1);
+        self.keyboard_ctrl.new_tick();
+        self.gamepad_ctrl.new_tick();
+
+        let mut events = Vec::new();
+        for input in self.keyboard_receiver.try_iter() {
             self.keyboard_ctrl.input_event(&input);
-            Ok(Some(keyboard_event_handler(&input)))
-        } else if let Some(event) = gilrs.next_event() {
+            events.push(EventKind::Keyboard(input));
+        }
+        let gilrs = &mut *self.gilrs.borrow_mut();
+        while let Some(event) = gilrs.next_event() {
             self.gamepad_ctrl.input_event(gilrs, &event);
-            Ok(Some(gamepad_event_handler(gilrs, &event)))
-        } else {
-            Ok(None)
+            events.push(EventKind::Gamepad(event));
         }
+        events.extend(self.pointer_receiver.try_iter().map(EventKind::Pointer));
+        let events = events
+            .into_iter()
+            .map(|kind| InputEvent { tick, kind })
+            .collect();
+        EventBatch { events }
     }
 
-    pub fn create_controller_red(&self) -> impl Controller {
-        self.keyboard_ctrl.create_sub_controller([
-            Key::LogicKey(VirtualKeyCode::E),
-            Key::LogicKey(VirtualKeyCode::D),
-            Key::LogicKey(VirtualKeyCode::S),
-            Key::LogicKey(VirtualKeyCode::F),
-        ])
+    pub fn gamepad_name(&self, id: GamepadId) -> String {
+        self.gilrs.borrow().gamepad(id).name().to_string()
     }
-    pub fn create_controller_green(&self) -> impl Controller {
-        self.keyboard_ctrl.create_sub_controller([
-            Key::LogicKey(VirtualKeyCode::Up),
-            Key::LogicKey(VirtualKeyCode::Down),
-            Key::LogicKey(VirtualKeyCode::Left),
-            Key::LogicKey(VirtualKeyCode::Right),
-        ])
+
+    pub fn key_bindings(&self) -> &KeyBindings {
+        &self.key_bindings
+    }
+
+    /// A controller of the keyboard cluster with this index in the key bindings.
+    pub fn create_keyboard_controller(&self, cluster: usize) -> impl Controller {
+        let cluster = &self.key_bindings.clusters[cluster];
+        self.keyboard_ctrl.create_sub_controller(cluster.movement_keys(), cluster.fire)
     }
     pub fn create_gamepad_controller(&self, id: GamepadId) -> impl Controller {
         self.gamepad_ctrl.create_gamepad_controller(id)
     }
+
+    pub fn create_controller(&self, source: Source) -> Box<dyn Controller> {
+        match source {
+            Source::Keyboard(cluster) => Box::new(self.create_keyboard_controller(cluster)),
+            Source::Gamepad(id) => Box::new(self.create_gamepad_controller(id)),
+            Source::Bot(difficulty) => Box::new(Bot::new(difficulty)),
+        }
+    }
+
+    /// The source joining by the event: the fire key of a keyboard cluster, the south button
+    /// of a gamepad, or number keys 1, 2 and 3 adding an easy, normal and hard bot.
+    pub fn joining(&self, event: &EventKind) -> Option<Source> {
+        match *event {
+            EventKind::Keyboard(input) if input.state == ElementState::Pressed => {
+                if let Some(cluster) = self.key_bindings.joining(&input) {
+                    return Some(Source::Keyboard(cluster));
+                }
+                match input.virtual_keycode? {
+                    VirtualKeyCode::Key1 => Some(Source::Bot(Difficulty::Easy)),
+                    VirtualKeyCode::Key2 => Some(Source::Bot(Difficulty::Normal)),
+                    VirtualKeyCode::Key3 => Some(Source::Bot(Difficulty::Hard)),
+                    _ => None,
+                }
+            }
+            EventKind::Gamepad(gilrs::Event {
+                id,
+                event: gilrs::EventType::ButtonPressed(gilrs::Button::South, ..),
+                ..
+            }) => Some(Source::Gamepad(id)),
+            _ => None,
+        }
+    }
+}
+
+impl EventBatch {
+    /// Keys pressed in this tick, in the order they are pressed.
+    pub fn pressed_keys(&self) -> impl Iterator<Item = VirtualKeyCode> + '_ {
+        self.events.iter().filter_map(|event| match event.kind {
+            EventKind::Keyboard(KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode,
+                ..
+            }) => virtual_keycode,
+            _ => None,
+        })
+    }
 }
 
 impl InputEventSender {
     pub fn window_event(&mut self, event: &WindowEvent) {
-        if let WindowEvent::KeyboardInput { input, .. } = event {
-            self.keyboard_sender.send(*input).unwrap_or(());
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                self.keyboard_sender.send(*input).unwrap_or(());
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let event = PointerEvent::Moved {
+                    position: [position.x as f32, position.y as f32],
+                    frame_size: self.frame_size,
+                };
+                self.pointer_sender.send(event).unwrap_or(());
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => {
+                let event = PointerEvent::Pressed(*button);
+                self.pointer_sender.send(event).unwrap_or(());
+            }
+            _ => {}
         }
     }
+
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.frame_size = [size.width as f32, size.height as f32];
+    }
 }
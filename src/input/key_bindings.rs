@@ -0,0 +1,195 @@
+//! Keys of the players sharing the keyboard, loaded from a settings file in RON.
+//!
+//! A key is either `LogicKey(E)`, the key producing that character in the current layout,
+//! or `PhysicKey(18)`, the key at that position whatever the layout is, by its scan code.
+//! The file is written with the default bindings if it doesn't exist.
+use std::{error::Error, fmt, fs, io, path::Path};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled, warn};
+use serde::{Deserialize, Serialize};
+use winit::event::{KeyboardInput, VirtualKeyCode};
+
+use super::keyboard_controller::Key;
+
+/// The settings file if `--bindings` is not given, in the working directory.
+pub const DEFAULT_PATH: &str = "key_bindings.ron";
+
+const HEADER: &str = "\
+// Key bindings of the players sharing the keyboard, pressing the fire key joins a match.
+// A key is LogicKey(<key name>) by the character it types, or PhysicKey(<scan code>)
+// by its position on the keyboard, which works the same on any layout.
+";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    pub clusters: Vec<KeyCluster>,
+}
+
+/// The keys of one player.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyCluster {
+    /// Shown in logs.
+    pub name: String,
+    pub forward: Key,
+    pub backward: Key,
+    pub left: Key,
+    pub right: Key,
+    /// Also joins a match with this cluster.
+    pub fire: Key,
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(io::Error),
+    Ron(ron::Error),
+    /// Keys bound to more than one action.
+    Conflicts(Vec<String>),
+}
+
+impl KeyBindings {
+    /// Load the bindings, or write the default ones to the file if it doesn't exist.
+    pub fn load_or_create(path: &Path) -> Result<KeyBindings, BindingsError> {
+        let bindings = match fs::read_to_string(path) {
+            Ok(text) => ron::de::from_str(&text)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let bindings = KeyBindings::default();
+                match bindings.save(path) {
+                    Ok(()) => info!("Default key bindings are written to {}", path.display()),
+                    Err(err) => warn!("Failed to write {}: {}", path.display(), err),
+                }
+                bindings
+            }
+            Err(err) => return Err(err.into()),
+        };
+        bindings.validate()?;
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new()).unwrap();
+        fs::write(path, format!("{}{}\n", HEADER, text))
+    }
+
+    /// Every key is bound to one action at most.
+    pub fn validate(&self) -> Result<(), BindingsError> {
+        let mut bound: Vec<(&Key, String)> = Vec::new();
+        let mut conflicts = Vec::new();
+        for cluster in self.clusters.iter() {
+            for &(action, key) in cluster.actions().iter() {
+                let action = format!("{} of {}", action, cluster.name);
+                if let Some((_, other)) = bound.iter().find(|(other, _)| *other == key) {
+                    conflicts.push(format!("{:?} is bound to both {} and {}", key, other, action));
+                }
+                bound.push((key, action));
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(BindingsError::Conflicts(conflicts))
+        }
+    }
+
+    /// Index of the cluster whose fire key is pressed in the event.
+    pub fn joining(&self, input: &KeyboardInput) -> Option<usize> {
+        self.clusters
+            .iter()
+            .position(|cluster| cluster.fire.matches(input))
+    }
+}
+
+impl KeyCluster {
+    /// Movement keys in the order `Keyboard::create_sub_controller` takes them.
+    pub fn movement_keys(&self) -> [Key; 4] {
+        [self.forward, self.backward, self.left, self.right]
+    }
+
+    fn actions(&self) -> [(&'static str, &Key); 5] {
+        [
+            ("forward", &self.forward),
+            ("backward", &self.backward),
+            ("left", &self.left),
+            ("right", &self.right),
+            ("fire", &self.fire),
+        ]
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let cluster = |name: &str, keys: [VirtualKeyCode; 5]| KeyCluster {
+            name: name.to_string(),
+            forward: Key::LogicKey(keys[0]),
+            backward: Key::LogicKey(keys[1]),
+            left: Key::LogicKey(keys[2]),
+            right: Key::LogicKey(keys[3]),
+            fire: Key::LogicKey(keys[4]),
+        };
+        KeyBindings {
+            clusters: vec![
+                cluster("ESDF", [E, D, S, F, Q]),
+                cluster("arrows", [Up, Down, Left, Right, M]),
+                cluster("IJKL", [I, K, J, L, U]),
+                cluster("numpad", [Numpad8, Numpad5, Numpad4, Numpad6, Numpad0]),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Io(err) => write!(f, "{}", err),
+            BindingsError::Ron(err) => write!(f, "{}", err),
+            BindingsError::Conflicts(conflicts) => write!(f, "{}", conflicts.join("\n")),
+        }
+    }
+}
+
+impl Error for BindingsError {}
+
+impl From<io::Error> for BindingsError {
+    fn from(err: io::Error) -> Self {
+        BindingsError::Io(err)
+    }
+}
+
+impl From<ron::Error> for BindingsError {
+    fn from(err: ron::Error) -> Self {
+        BindingsError::Ron(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_round_trip() {
+        let bindings = KeyBindings::default();
+        assert!(bindings.validate().is_ok());
+        let text = ron::ser::to_string_pretty(&bindings, ron::ser::PrettyConfig::new()).unwrap();
+        let text = format!("{}{}", HEADER, text);
+        assert_eq!(ron::de::from_str::<KeyBindings>(&text).unwrap(), bindings);
+    }
+
+    #[test]
+    fn conflicts_are_reported() {
+        let mut bindings = KeyBindings::default();
+        bindings.clusters[1].fire = Key::LogicKey(VirtualKeyCode::E);
+        bindings.clusters[1].left = Key::PhysicKey(30);
+        bindings.clusters[1].right = Key::PhysicKey(30);
+        match bindings.validate() {
+            Err(BindingsError::Conflicts(conflicts)) => assert_eq!(
+                conflicts,
+                vec![
+                    "PhysicKey(30) is bound to both left of arrows and right of arrows",
+                    "LogicKey(E) is bound to both forward of ESDF and fire of arrows",
+                ]
+            ),
+            _ => panic!("conflicts are not found"),
+        }
+    }
+}
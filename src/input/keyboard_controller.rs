@@ -1,24 +1,52 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt,
     sync::{Arc, Mutex},
 };
 
+use serde::{Deserialize, Serialize};
 use winit::event::{ElementState, KeyboardInput, ScanCode, VirtualKeyCode};
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Key {
     LogicKey(VirtualKeyCode),
     PhysicKey(ScanCode),
 }
 
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::LogicKey(code) => write!(f, "{:?}", code),
+            Key::PhysicKey(scancode) => write!(f, "key #{}", scancode),
+        }
+    }
+}
+
+impl Key {
+    /// Whether the event is of this key.
+    pub fn matches(&self, input: &KeyboardInput) -> bool {
+        match *self {
+            Key::LogicKey(code) => input.virtual_keycode == Some(code),
+            Key::PhysicKey(scancode) => input.scancode == scancode,
+        }
+    }
+}
+
 pub struct Keyboard {
-    key_map: Arc<Mutex<HashMap<Key, ElementState>>>,
+    key_map: Arc<Mutex<KeyMap>>,
+}
+
+#[derive(Default)]
+struct KeyMap {
+    states: HashMap<Key, ElementState>,
+    /// Keys pressed in this tick, a key pressed and released in one tick still counts.
+    pressed: HashSet<Key>,
 }
 
 impl Keyboard {
     pub fn new() -> Keyboard {
         Keyboard {
-            key_map: Arc::new(Mutex::new(HashMap::new())),
+            key_map: Arc::new(Mutex::new(KeyMap::default())),
         }
     }
     pub fn input_event(&self, e: &KeyboardInput) {
@@ -29,15 +57,25 @@ impl Keyboard {
             ..
         } = e;
         let key_map = &mut *self.key_map.lock().unwrap();
-        key_map.insert(Key::PhysicKey(*scancode), *state);
-        if let Some(code) = virtual_keycode {
-            key_map.insert(Key::LogicKey(*code), *state);
+        let mut keys = vec![Key::PhysicKey(*scancode)];
+        keys.extend(virtual_keycode.map(Key::LogicKey));
+        for key in keys {
+            key_map.states.insert(key, *state);
+            if *state == ElementState::Pressed {
+                key_map.pressed.insert(key);
+            }
         }
     }
 
-    pub fn create_sub_controller(&self, movement_keys: [Key; 4]) -> Controller {
+    /// Forget keys pressed in the last tick.
+    pub fn new_tick(&self) {
+        self.key_map.lock().unwrap().pressed.clear();
+    }
+
+    pub fn create_sub_controller(&self, movement_keys: [Key; 4], fire_key: Key) -> Controller {
         Controller {
             movement_keys,
+            fire_key,
             key_map: self.key_map.clone(),
         }
     }
@@ -45,19 +83,68 @@ impl Keyboard {
 
 pub struct Controller {
     movement_keys: [Key; 4],
-    key_map: Arc<Mutex<HashMap<Key, ElementState>>>,
+    fire_key: Key,
+    key_map: Arc<Mutex<KeyMap>>,
+}
+
+impl KeyMap {
+    fn is_down(&self, key: &Key) -> bool {
+        self.pressed.contains(key) || self.states.get(key) == Some(&ElementState::Pressed)
+    }
 }
 
 impl super::Controller for Controller {
     fn movement_status(&self) -> (f32, f32) {
         let key_map = &*self.key_map.lock().unwrap();
-        let get_value = |key, pressed| match key_map.get(&self.movement_keys[key]) {
-            Some(ElementState::Pressed) => pressed,
-            _ => 0.0,
+        let get_value = |key, pressed| {
+            if key_map.is_down(&self.movement_keys[key]) {
+                pressed
+            } else {
+                0.0
+            }
         };
         (
             get_value(3, 1.0) - get_value(2, 1.0),
             get_value(0, 1.0) - get_value(1, 0.6),
         )
     }
+
+    fn fire_status(&self) -> bool {
+        self.key_map.lock().unwrap().is_down(&self.fire_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Controller as _;
+    use super::*;
+
+    #[allow(deprecated)]
+    fn event(code: VirtualKeyCode, state: ElementState) -> KeyboardInput {
+        KeyboardInput {
+            scancode: 16,
+            state,
+            virtual_keycode: Some(code),
+            modifiers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn short_press_lasts_a_tick() {
+        let keyboard = Keyboard::new();
+        let controller = keyboard.create_sub_controller(
+            [
+                Key::LogicKey(VirtualKeyCode::E),
+                Key::LogicKey(VirtualKeyCode::D),
+                Key::LogicKey(VirtualKeyCode::S),
+                Key::LogicKey(VirtualKeyCode::F),
+            ],
+            Key::PhysicKey(16),
+        );
+        keyboard.input_event(&event(VirtualKeyCode::Q, ElementState::Pressed));
+        keyboard.input_event(&event(VirtualKeyCode::Q, ElementState::Released));
+        assert!(controller.fire_status());
+        keyboard.new_tick();
+        assert!(!controller.fire_status());
+    }
 }
@@ -1,11 +1,55 @@
+use rapier2d::math::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::scene::maze::Maze;
+
+pub mod bot_controller;
 pub mod gamepad_controller;
 pub mod input_center;
+pub mod key_bindings;
 pub mod keyboard_controller;
+pub mod network_controller;
 
 /// 控制器代表用于操控一辆坦克的对象，可以是一个手柄或者一个键盘，甚至一个A.I.。
 /// 一般拥有一个movement_status方法用于查询当前该控制器的输入状态
 /// 包括一个指定旋转操作的浮点数，以及一个指定前进、后退操作的浮点数
 /// 两者的取值范围都在[-1.0 .. 1.0]之间
+/// 以及一个fire_status方法用于查询开火键当前是否被按下
+/// 每个物理帧开始时会调用observe方法，A.I.可以从中读取当前的世界状态
+/// 随后调用input方法取得这一帧的输入
 pub trait Controller: Sync + Send {
     fn movement_status(&self) -> (f32, f32);
+    fn fire_status(&self) -> bool;
+    fn observe(&self, _world: &WorldView) {}
+    fn input(&self) -> PlayerInput {
+        PlayerInput {
+            movement: self.movement_status(),
+            fire: self.fire_status(),
+        }
+    }
+}
+
+/// The input of one player in one physic tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInput {
+    /// Rotation and acceleration, the same as [`Controller::movement_status`].
+    pub movement: (f32, f32),
+    pub fire: bool,
+}
+
+/// A read-only snapshot of the game world, given to controllers every physic tick.
+pub struct WorldView<'a> {
+    pub maze: &'a Maze,
+    /// All tanks of the match, in the order of players.
+    pub tanks: &'a [TankPose],
+    /// Index of the tank driven by the controller in `tanks`.
+    pub me: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TankPose {
+    pub position: Point<f32>,
+    /// The tank faces `(-sin, cos)` of this angle, which is `+y` at zero.
+    pub rotation: f32,
+    pub alive: bool,
 }
@@ -0,0 +1,129 @@
+use std::{
+    sync::{Arc, Mutex},
+    time,
+};
+
+use crate::net::quality::{ConnectionQuality, LinkMonitor};
+
+use super::{Controller, PlayerInput};
+
+/// A player on another computer, whose inputs arrive from the network.
+///
+/// Clones share the same input stream, so the network end feeds one clone while
+/// the physic thread reads another.
+#[derive(Clone)]
+pub struct NetworkController {
+    stream: Arc<Mutex<InputStream>>,
+}
+
+struct InputStream {
+    monitor: LinkMonitor,
+    latest: PlayerInput,
+    /// A press arrived after the last tick. It's kept for the next tick even if
+    /// the release arrives together, or the shot would be lost.
+    pending_fire: bool,
+}
+
+impl NetworkController {
+    pub fn new() -> NetworkController {
+        NetworkController {
+            stream: Arc::new(Mutex::new(InputStream {
+                monitor: LinkMonitor::new(time::Instant::now()),
+                latest: PlayerInput::default(),
+                pending_fire: false,
+            })),
+        }
+    }
+
+    /// Inputs older than the latest one arrive late, they are dropped.
+    pub fn receive(&self, sequence: u32, input: PlayerInput, now: time::Instant) {
+        let stream = &mut *self.stream.lock().unwrap();
+        if stream.monitor.arrive(sequence, now) {
+            stream.latest = input;
+            stream.pending_fire |= input.fire;
+        }
+    }
+
+    /// The peer left, the tank stops at once.
+    pub fn disconnect(&self) {
+        self.stream.lock().unwrap().monitor.close();
+    }
+
+    pub fn quality(&self) -> ConnectionQuality {
+        self.stream.lock().unwrap().monitor.quality(time::Instant::now())
+    }
+
+    /// The last known input while inputs are missing, nothing after disconnected.
+    fn input_at(&self, now: time::Instant) -> PlayerInput {
+        let stream = &mut *self.stream.lock().unwrap();
+        if !stream.monitor.quality(now).connected {
+            return PlayerInput::default();
+        }
+        let fire = stream.latest.fire || stream.pending_fire;
+        stream.pending_fire = false;
+        PlayerInput {
+            movement: stream.latest.movement,
+            fire,
+        }
+    }
+}
+
+impl Controller for NetworkController {
+    fn movement_status(&self) -> (f32, f32) {
+        self.stream.lock().unwrap().latest.movement
+    }
+
+    fn fire_status(&self) -> bool {
+        self.stream.lock().unwrap().latest.fire
+    }
+
+    fn input(&self) -> PlayerInput {
+        self.input_at(time::Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::quality::DISCONNECT_TIMEOUT;
+
+    use super::*;
+
+    fn input(rot: f32, fire: bool) -> PlayerInput {
+        PlayerInput {
+            movement: (rot, 1.0),
+            fire,
+        }
+    }
+
+    #[test]
+    fn late_inputs_are_dropped() {
+        let now = time::Instant::now();
+        let controller = NetworkController::new();
+        controller.receive(1, input(1.0, false), now);
+        controller.receive(0, input(-1.0, false), now);
+        assert_eq!(controller.input_at(now), input(1.0, false));
+        // Still the last known input when the next ones are lost.
+        assert_eq!(controller.input_at(now), input(1.0, false));
+    }
+
+    #[test]
+    fn short_press_is_kept() {
+        let now = time::Instant::now();
+        let controller = NetworkController::new();
+        controller.receive(0, input(0.0, true), now);
+        controller.receive(1, input(0.0, false), now);
+        assert!(controller.input_at(now).fire);
+        assert!(!controller.input_at(now).fire);
+    }
+
+    #[test]
+    fn disconnected_player_stops() {
+        let now = time::Instant::now();
+        let controller = NetworkController::new();
+        controller.receive(0, input(1.0, true), now);
+        assert_eq!(controller.input_at(now + DISCONNECT_TIMEOUT), PlayerInput::default());
+        controller.receive(1, input(1.0, true), now);
+        controller.disconnect();
+        assert_eq!(controller.input_at(now), PlayerInput::default());
+    }
+}
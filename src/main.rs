@@ -9,7 +9,11 @@ use winit::{
     window::{Fullscreen, WindowBuilder},
 };
 
+use input::key_bindings::{self, KeyBindings};
+
 mod input;
+mod net;
+mod options;
 mod scene;
 mod window;
 
@@ -23,6 +27,23 @@ fn abort(err: &dyn Error) -> ! {
 fn main() {
     // Init logger
     env_logger::init();
+    let mut options = options::Options::from_args().unwrap_or_else(|e| abort(&e));
+    if let Some(address) = &options.server {
+        net::server::serve(address, &options).unwrap_or_else(|e| abort(e.as_ref()));
+        return;
+    }
+    if let Some(address) = &options.host {
+        let address = net::server::spawn(address, &options).unwrap_or_else(|e| abort(&e));
+        options.connect = Some(address);
+    }
+    let bindings_path = options
+        .bindings
+        .clone()
+        .unwrap_or_else(|| key_bindings::DEFAULT_PATH.into());
+    let key_bindings = KeyBindings::load_or_create(&bindings_path).unwrap_or_else(|e| {
+        let message = format!("Invalid key bindings in {}:\n{}", bindings_path.display(), e);
+        abort(Box::<dyn Error>::from(message).as_ref())
+    });
     // Create window
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
@@ -30,10 +51,13 @@ fn main() {
         .unwrap_or_else(|e| abort(&e));
     info!("Successfully create window");
     let mut window_state =
-        block_on(window::WindowState::new(&window)).unwrap_or_else(|e| abort(e.as_ref()));
+        block_on(window::WindowState::new(&window, options, key_bindings))
+            .unwrap_or_else(|e| abort(e.as_ref()));
 
     event_loop.run(move |event, _, control_flow| {
-        window_state.update();
+        if !window_state.update() {
+            *control_flow = ControlFlow::Exit;
+        }
         match event {
             Event::WindowEvent {
                 ref event,
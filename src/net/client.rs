@@ -0,0 +1,141 @@
+//! The client end of a LAN match.
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    net::{ToSocketAddrs, UdpSocket},
+    time,
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+
+use crate::input::PlayerInput;
+
+use super::protocol::{
+    self, ClientMessage, Seat, ServerMessage, MAX_PACKET_SIZE, PROTOCOL_VERSION,
+};
+
+/// How long to wait for the welcome message before asking again.
+const JOIN_RETRY: time::Duration = time::Duration::from_millis(200);
+const JOIN_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+pub struct Client {
+    socket: UdpSocket,
+    /// Index of the first local player in the match, told by the lobby.
+    first: usize,
+    /// Sequence number of the next input message.
+    sequence: u32,
+    buffer: Vec<u8>,
+}
+
+impl Client {
+    /// Enter the lobby on the server.
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Client, Box<dyn Error>> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(address)?;
+        socket.set_read_timeout(Some(JOIN_RETRY))?;
+        let join = protocol::encode(&ClientMessage::Join {
+            version: PROTOCOL_VERSION,
+        });
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        let deadline = time::Instant::now() + JOIN_TIMEOUT;
+        while time::Instant::now() < deadline {
+            socket.send(&join)?;
+            let len = match socket.recv(&mut buffer) {
+                Ok(len) => len,
+                // No answer yet, or the server isn't started.
+                Err(err) if is_temporary(&err) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            match protocol::decode(&buffer[..len]) {
+                Some(ServerMessage::Welcome) => {
+                    socket.set_nonblocking(true)?;
+                    return Ok(Client {
+                        socket,
+                        first: 0,
+                        sequence: 0,
+                        buffer,
+                    });
+                }
+                Some(ServerMessage::Refused(reason)) => {
+                    return Err(format!("The server refused to join: {}", reason).into());
+                }
+                _ => {}
+            }
+        }
+        Err("The server doesn't answer".into())
+    }
+
+    pub fn first_player(&self) -> usize {
+        self.first
+    }
+
+    /// Local players in the lobby, sent again and again until the match starts.
+    pub fn send_seats(&self, seats: Vec<Seat>) -> io::Result<()> {
+        self.send(&ClientMessage::Seats(seats))
+    }
+
+    /// Ask the server to start the match, only the host can.
+    pub fn start(&self) -> io::Result<()> {
+        self.send(&ClientMessage::Start)
+    }
+
+    /// Send the inputs of local players, `round` is the round of the maze this client has.
+    pub fn send_inputs(&mut self, round: u32, inputs: Vec<PlayerInput>) -> io::Result<()> {
+        let sequence = self.sequence;
+        self.sequence += 1;
+        self.send(&ClientMessage::Input {
+            sequence,
+            round,
+            inputs,
+        })
+    }
+
+    /// Messages received since last call, except welcome messages.
+    pub fn receive(&mut self) -> io::Result<Vec<ServerMessage>> {
+        let mut messages = Vec::new();
+        loop {
+            let len = match self.socket.recv(&mut self.buffer) {
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(messages),
+                Err(err) if is_temporary(&err) => continue,
+                Err(err) => return Err(err),
+            };
+            match protocol::decode(&self.buffer[..len]) {
+                Some(ServerMessage::Welcome) => {}
+                Some(message) => {
+                    if let ServerMessage::Lobby(lobby) = &message {
+                        self.first = lobby.first;
+                    }
+                    messages.push(message)
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn send(&self, message: &ClientMessage) -> io::Result<()> {
+        match self.socket.send(&protocol::encode(message)) {
+            Err(err) if !is_temporary(&err) => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Errors caused by a server which isn't there for now.
+fn is_temporary(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+    )
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.send(&ClientMessage::Leave)
+            .unwrap_or_else(|err| error!("Failed to leave the match: {}", err));
+    }
+}
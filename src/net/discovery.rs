@@ -0,0 +1,106 @@
+//! Find games hosted on the LAN by UDP broadcast.
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time,
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+
+use super::protocol::{
+    self, ClientMessage, GameInfo, ServerMessage, MAX_PACKET_SIZE, PROTOCOL_VERSION,
+};
+
+/// Servers on this port can be found, browsers look for games on it.
+pub const DEFAULT_PORT: u16 = 7777;
+const SEARCH_INTERVAL: time::Duration = time::Duration::from_secs(1);
+/// A game which hasn't answered for this long is gone.
+const GAME_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+
+pub struct HostedGame {
+    pub address: SocketAddr,
+    pub info: GameInfo,
+    last_seen: time::Instant,
+}
+
+pub struct Browser {
+    socket: UdpSocket,
+    port: u16,
+    games: Vec<HostedGame>,
+    last_search: Option<time::Instant>,
+    buffer: Vec<u8>,
+}
+
+impl Browser {
+    pub fn new(port: u16) -> io::Result<Browser> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Browser {
+            socket,
+            port,
+            games: Vec::new(),
+            last_search: None,
+            buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    /// Ask for games from time to time and collect the answers, returns true if the list changed.
+    pub fn update(&mut self) -> io::Result<bool> {
+        let now = time::Instant::now();
+        if self.last_search.map_or(true, |last| now - last >= SEARCH_INTERVAL) {
+            self.last_search = Some(now);
+            self.search()?;
+        }
+        let mut changed = false;
+        loop {
+            let (len, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err),
+            };
+            let info = match protocol::decode(&self.buffer[..len]) {
+                Some(ServerMessage::Game(info)) => info,
+                _ => continue,
+            };
+            match self.games.iter_mut().find(|game| game.info.id == info.id) {
+                Some(game) => {
+                    changed |= game.info != info;
+                    game.info = info;
+                    game.last_seen = now;
+                }
+                None => {
+                    changed = true;
+                    self.games.push(HostedGame {
+                        address,
+                        info,
+                        last_seen: now,
+                    });
+                }
+            }
+        }
+        let count = self.games.len();
+        self.games.retain(|game| now - game.last_seen < GAME_TIMEOUT);
+        Ok(changed || count != self.games.len())
+    }
+
+    /// Games found, in the order they first answered.
+    pub fn games(&self) -> &[HostedGame] {
+        &self.games
+    }
+
+    fn search(&self) -> io::Result<()> {
+        let discover = protocol::encode(&ClientMessage::Discover {
+            version: PROTOCOL_VERSION,
+        });
+        // Broadcasts may not come back to this computer, so ask it directly too.
+        self.socket.send_to(&discover, (Ipv4Addr::LOCALHOST, self.port))?;
+        if let Err(err) = self.socket.send_to(&discover, (Ipv4Addr::BROADCAST, self.port)) {
+            // There may be no network at all.
+            debug!("Failed to broadcast: {}", err);
+        }
+        Ok(())
+    }
+}
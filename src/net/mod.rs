@@ -0,0 +1,11 @@
+//! Playing over the LAN. Games are found by broadcast, players gather in the lobby of a server.
+//! The server runs the only simulation of the match, clients send the inputs of their players
+//! and draw the snapshots they receive.
+//! Or without a server, every peer simulates the match with rollback.
+pub mod client;
+pub mod discovery;
+pub mod peer;
+pub mod protocol;
+pub mod quality;
+pub mod rollback;
+pub mod server;
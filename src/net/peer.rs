@@ -0,0 +1,249 @@
+//! A peer of a rollback match, exchanging the inputs of its player with other peers.
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, UdpSocket},
+    time,
+};
+
+use crate::input::PlayerInput;
+
+use super::{
+    protocol::{self, PeerInputs, MAX_PACKET_SIZE},
+    quality::{ConnectionQuality, LinkMonitor},
+    rollback::Rollback,
+};
+
+/// Inputs not acknowledged are sent again, but no more than this in a message.
+const MAX_INPUTS_PER_MESSAGE: usize = 64;
+
+pub struct Peer {
+    socket: UdpSocket,
+    /// Index of the local player, which is the index of this peer.
+    player: usize,
+    /// Addresses of all peers in the order of their players, including this one.
+    addresses: Vec<SocketAddr>,
+    /// Inputs of the local player from the first tick.
+    local_inputs: Vec<PlayerInput>,
+    /// The first tick whose local input each peer is missing.
+    acks: Vec<u32>,
+    monitors: Vec<LinkMonitor>,
+    sequence: u32,
+    buffer: Vec<u8>,
+}
+
+impl Peer {
+    /// Take the address of the player.
+    pub fn bind(addresses: Vec<SocketAddr>, player: usize) -> io::Result<Peer> {
+        let socket = UdpSocket::bind(addresses[player])?;
+        Peer::new(socket, addresses, player)
+    }
+
+    pub fn new(socket: UdpSocket, addresses: Vec<SocketAddr>, player: usize) -> io::Result<Peer> {
+        socket.set_nonblocking(true)?;
+        let now = time::Instant::now();
+        Ok(Peer {
+            socket,
+            player,
+            acks: vec![0; addresses.len()],
+            monitors: addresses.iter().map(|_| LinkMonitor::new(now)).collect(),
+            addresses,
+            local_inputs: Vec::new(),
+            sequence: 0,
+            buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// The input of the local player at the next tick of the rollback.
+    pub fn input(&mut self, rollback: &mut Rollback, input: PlayerInput) {
+        let tick = self.local_inputs.len() as u32;
+        debug_assert_eq!(tick, rollback.tick());
+        self.local_inputs.push(input);
+        rollback.add_input(self.player, tick, input);
+    }
+
+    /// Send local inputs the other peers are missing.
+    pub fn send(&mut self, rollback: &Rollback) -> io::Result<()> {
+        for (player, &address) in self.addresses.iter().enumerate() {
+            if player == self.player {
+                continue;
+            }
+            let first = (self.acks[player] as usize).min(self.local_inputs.len());
+            let end = (first + MAX_INPUTS_PER_MESSAGE).min(self.local_inputs.len());
+            let message = PeerInputs {
+                sequence: self.sequence,
+                first: first as u32,
+                inputs: self.local_inputs[first..end].to_vec(),
+                ack: rollback.known_until(player),
+            };
+            match self.socket.send_to(&protocol::encode(&message), address) {
+                Err(err) if !is_temporary(&err) => return Err(err),
+                _ => {}
+            }
+        }
+        self.sequence += 1;
+        Ok(())
+    }
+
+    /// Give the received remote inputs to the rollback.
+    pub fn receive(&mut self, rollback: &mut Rollback) -> io::Result<()> {
+        loop {
+            let (len, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if is_temporary(&err) => continue,
+                Err(err) => return Err(err),
+            };
+            let player = match self.addresses.iter().position(|&a| a == address) {
+                Some(player) if player != self.player => player,
+                _ => continue,
+            };
+            let message: PeerInputs = match protocol::decode(&self.buffer[..len]) {
+                Some(message) => message,
+                None => continue,
+            };
+            // Late messages still carry useful inputs.
+            self.monitors[player].arrive(message.sequence, time::Instant::now());
+            self.acks[player] = self.acks[player].max(message.ack);
+            for (tick, input) in (message.first..).zip(message.inputs) {
+                rollback.add_input(player, tick, input);
+            }
+        }
+    }
+
+    pub fn quality(&self, player: usize) -> ConnectionQuality {
+        self.monitors[player].quality(time::Instant::now())
+    }
+}
+
+/// Errors caused by a peer which isn't there for now.
+fn is_temporary(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::WouldBlock | ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crossbeam_channel::{bounded, Sender};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::scene::game_scene::{simulation::Simulation, WallColliders};
+
+    use super::*;
+
+    /// Forwards datagrams between two peers on loopback, each one is delayed by
+    /// `delay` plus a random jitter up to `jitter`, so some of them arrive out of order.
+    struct Link {
+        address: SocketAddr,
+        stop: Sender<()>,
+    }
+
+    impl Link {
+        fn new(a: SocketAddr, b: SocketAddr, delay: Duration, jitter: Duration) -> Link {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+            let address = socket.local_addr().unwrap();
+            let (stop, stopped) = bounded(1);
+            thread::spawn(move || {
+                let mut rng = ChaCha8Rng::seed_from_u64(0);
+                let mut queue: VecDeque<(Instant, Vec<u8>, SocketAddr)> = VecDeque::new();
+                let mut buffer = vec![0; MAX_PACKET_SIZE];
+                while stopped.try_recv().is_err() {
+                    if let Ok((len, from)) = socket.recv_from(&mut buffer) {
+                        let to = if from == a { b } else { a };
+                        let due = Instant::now() + delay + jitter.mul_f32(rng.gen());
+                        queue.push_back((due, buffer[..len].to_vec(), to));
+                    }
+                    let now = Instant::now();
+                    queue.retain(|(due, data, to)| {
+                        if *due > now {
+                            return true;
+                        }
+                        socket.send_to(data, to).unwrap();
+                        false
+                    });
+                }
+            });
+            Link { address, stop }
+        }
+    }
+
+    impl Drop for Link {
+        fn drop(&mut self) {
+            self.stop.send(()).unwrap();
+        }
+    }
+
+    fn scripted_input(player: usize, tick: u32) -> PlayerInput {
+        PlayerInput {
+            movement: (((tick / 30 + player as u32) % 3) as f32 - 1.0, 1.0),
+            fire: (tick + player as u32 * 11) % 40 < 2,
+        }
+    }
+
+    #[test]
+    fn peers_agree_with_delay_and_jitter() {
+        const TICKS: u32 = 900;
+        let sockets = [
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+        ];
+        let link = Link::new(
+            sockets[0].local_addr().unwrap(),
+            sockets[1].local_addr().unwrap(),
+            Duration::from_millis(40),
+            Duration::from_millis(30),
+        );
+        let mut peers: Vec<(Peer, Rollback)> = sockets
+            .iter()
+            .enumerate()
+            .map(|(player, socket)| {
+                // Both peers see the other one at the address of the link.
+                let mut addresses = vec![link.address; 2];
+                addresses[player] = socket.local_addr().unwrap();
+                let peer = Peer::new(socket.try_clone().unwrap(), addresses, player).unwrap();
+                let simulation = Simulation::new(2, 9, None, WallColliders::Cuboids);
+                (peer, Rollback::new(simulation))
+            })
+            .collect();
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while peers
+            .iter()
+            .any(|(peer, rollback)| rollback.known_until(1 - peer.player()) < TICKS)
+        {
+            assert!(Instant::now() < deadline, "peers never agree");
+            for (peer, rollback) in peers.iter_mut() {
+                peer.receive(rollback).unwrap();
+                if rollback.tick() < TICKS && !rollback.stalled() {
+                    let player = peer.player();
+                    peer.input(rollback, scripted_input(player, rollback.tick()));
+                    rollback.advance();
+                }
+                peer.send(rollback).unwrap();
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+
+        let mut expected = Simulation::new(2, 9, None, WallColliders::Cuboids);
+        for tick in 0..TICKS {
+            expected.step(&[scripted_input(0, tick), scripted_input(1, tick)]);
+        }
+        for (_, rollback) in peers.iter_mut() {
+            rollback.rewind();
+            assert_eq!(rollback.simulation().snapshot(), expected.snapshot());
+        }
+    }
+}
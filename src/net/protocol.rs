@@ -0,0 +1,96 @@
+//! Messages between the server and clients, each UDP datagram carries one message in bincode.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::input::PlayerInput;
+use crate::scene::{game_scene::simulation::Snapshot, maze::Maze};
+
+/// Clients and servers of different versions refuse each other.
+pub const PROTOCOL_VERSION: u16 = 2;
+/// The largest UDP payload.
+pub const MAX_PACKET_SIZE: usize = 65507;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Look for games, broadcast on the LAN. Servers of the same version answer with
+    /// [`ServerMessage::Game`].
+    Discover { version: u16 },
+    /// Enter the lobby, sent again until the server answers.
+    Join { version: u16 },
+    /// The local players of the client in the lobby, sent all the time before the match starts.
+    /// Players are added or removed by changing the length, those without a place are ignored.
+    Seats(Vec<Seat>),
+    /// Only the host can start the match, when all players are ready.
+    Start,
+    /// Inputs of the local players for the next tick, `sequence` increases in every message.
+    /// `round` is the round whose maze the client has, the server sends the maze again
+    /// until it's the current one.
+    Input {
+        sequence: u32,
+        round: u32,
+        inputs: Vec<PlayerInput>,
+    },
+    Leave,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ServerMessage {
+    /// The answer to [`ClientMessage::Discover`].
+    Game(GameInfo),
+    /// The client is in the lobby.
+    Welcome,
+    Refused(String),
+    /// Sent to every client all the time before the match starts.
+    /// The first maze or snapshot means the match has started.
+    Lobby(LobbyState),
+    Maze { round: u32, maze: Maze },
+    Snapshot(Snapshot),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Seat {
+    /// Index in [`COLOURS`](crate::scene::palette::COLOURS), the server changes it
+    /// if another player has it.
+    pub colour: usize,
+    pub ready: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LobbyState {
+    /// Players of all clients, in the order of the match.
+    pub seats: Vec<Seat>,
+    /// Index of the first local player of the receiver in `seats`.
+    pub first: usize,
+    /// Whether the receiver is the host.
+    pub host: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameInfo {
+    /// Chosen by the server at random, one game may answer from several addresses.
+    pub id: u64,
+    pub players: usize,
+    pub places: usize,
+    pub started: bool,
+}
+
+/// Sent between peers of a rollback match all the time, lost inputs are sent again.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerInputs {
+    /// Increases in every message.
+    pub sequence: u32,
+    pub first: u32,
+    /// Inputs of the sender's player, from the tick `first`.
+    pub inputs: Vec<PlayerInput>,
+    /// The first tick whose input of the receiver's player is missing on the sender.
+    pub ack: u32,
+}
+
+pub fn encode<T: Serialize>(message: &T) -> Vec<u8> {
+    // Messages contain no maps or unsized things, serializing them never fails.
+    bincode::serialize(message).unwrap()
+}
+
+/// Returns `None` for datagrams which aren't a message, they are dropped.
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Option<T> {
+    bincode::deserialize(data).ok()
+}
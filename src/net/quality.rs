@@ -0,0 +1,125 @@
+//! Watch a stream of numbered datagrams from a peer.
+use std::{fmt, time};
+
+/// A peer silent for longer than this is gone.
+pub const DISCONNECT_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// Weight of the newest datagram in the averaged ratios.
+const SMOOTHING: f32 = 0.05;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConnectionQuality {
+    /// Averaged ratio of datagrams skipped by a newer one.
+    pub loss: f32,
+    /// Averaged ratio of datagrams arriving after a newer one, they are dropped.
+    pub late: f32,
+    /// Time since the last datagram.
+    pub silence: time::Duration,
+    pub connected: bool,
+}
+
+pub struct LinkMonitor {
+    last_sequence: Option<u32>,
+    last_arrival: time::Instant,
+    loss: f32,
+    late: f32,
+    /// The peer said goodbye.
+    closed: bool,
+}
+
+impl LinkMonitor {
+    pub fn new(now: time::Instant) -> LinkMonitor {
+        LinkMonitor {
+            last_sequence: None,
+            last_arrival: now,
+            loss: 0.0,
+            late: 0.0,
+            closed: false,
+        }
+    }
+
+    /// Returns false if the datagram is not newer than the latest one, then it should be dropped.
+    pub fn arrive(&mut self, sequence: u32, now: time::Instant) -> bool {
+        self.last_arrival = now;
+        match self.last_sequence {
+            Some(last) if sequence <= last => {
+                self.late = average(self.late, 1.0);
+                false
+            }
+            last => {
+                let skipped = last.map_or(0, |last| sequence - last - 1);
+                // The ratio is almost 1 after this many, no need to count more.
+                for _ in 0..skipped.min(100) {
+                    self.loss = average(self.loss, 1.0);
+                }
+                self.loss = average(self.loss, 0.0);
+                self.late = average(self.late, 0.0);
+                self.last_sequence = Some(sequence);
+                true
+            }
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    pub fn quality(&self, now: time::Instant) -> ConnectionQuality {
+        let silence = now.saturating_duration_since(self.last_arrival);
+        ConnectionQuality {
+            loss: self.loss,
+            late: self.late,
+            silence,
+            connected: !self.closed && silence < DISCONNECT_TIMEOUT,
+        }
+    }
+}
+
+fn average(old: f32, new: f32) -> f32 {
+    old * (1.0 - SMOOTHING) + new * SMOOTHING
+}
+
+impl fmt::Display for ConnectionQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.connected {
+            return write!(f, "disconnected");
+        }
+        write!(
+            f,
+            "loss {:.0}%, late {:.0}%, silent for {}ms",
+            self.loss * 100.0,
+            self.late * 100.0,
+            self.silence.as_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn late_and_lost_datagrams() {
+        let start = time::Instant::now();
+        let mut monitor = LinkMonitor::new(start);
+        assert!(monitor.arrive(0, start));
+        assert!(monitor.arrive(3, start));
+        assert!(!monitor.arrive(2, start));
+        assert!(!monitor.arrive(3, start));
+        assert!(monitor.arrive(4, start));
+        let quality = monitor.quality(start);
+        assert!(quality.loss > 0.0 && quality.late > 0.0);
+        assert!(quality.connected);
+    }
+
+    #[test]
+    fn silent_peer_is_disconnected() {
+        let start = time::Instant::now();
+        let mut monitor = LinkMonitor::new(start);
+        assert!(monitor.quality(start + DISCONNECT_TIMEOUT / 2).connected);
+        assert!(!monitor.quality(start + DISCONNECT_TIMEOUT).connected);
+        monitor.arrive(0, start + DISCONNECT_TIMEOUT);
+        assert!(monitor.quality(start + DISCONNECT_TIMEOUT).connected);
+        monitor.close();
+        assert!(!monitor.quality(start + DISCONNECT_TIMEOUT).connected);
+    }
+}
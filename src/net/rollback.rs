@@ -0,0 +1,205 @@
+//! Rollback netcode: every peer runs the whole simulation without waiting for the others.
+//!
+//! Missing inputs of remote players are predicted to be their last known ones. When a real
+//! input differs from the prediction, the simulation goes back to that tick and runs again.
+use std::collections::VecDeque;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+
+use crate::input::PlayerInput;
+use crate::scene::game_scene::simulation::{Simulation, SimulationState, Snapshot};
+
+/// How many ticks the simulation may run ahead of the oldest missing input.
+pub const MAX_PREDICTION: u32 = 30;
+
+pub struct Rollback {
+    simulation: Simulation,
+    players: usize,
+    /// The oldest tick kept, inputs of all players before it are known.
+    base: u32,
+    /// The next tick to simulate.
+    tick: u32,
+    /// States before the ticks from `base` to `tick`, inclusive.
+    states: VecDeque<SimulationState>,
+    /// Inputs of every player at the ticks from `base`, `None` if not arrived yet.
+    inputs: VecDeque<Vec<Option<PlayerInput>>>,
+    /// Inputs of every player at the tick before `base`.
+    last_confirmed: Vec<PlayerInput>,
+    /// The earliest tick simulated with a wrong prediction.
+    rewind_to: Option<u32>,
+}
+
+impl Rollback {
+    pub fn new(simulation: Simulation) -> Rollback {
+        let players = simulation.players();
+        let mut states = VecDeque::new();
+        states.push_back(simulation.save());
+        Rollback {
+            simulation,
+            players,
+            base: 0,
+            tick: 0,
+            states,
+            inputs: VecDeque::new(),
+            last_confirmed: vec![PlayerInput::default(); players],
+            rewind_to: None,
+        }
+    }
+
+    /// The next tick to simulate.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn simulation(&self) -> &Simulation {
+        &self.simulation
+    }
+
+    /// The first tick whose input of the player is still missing.
+    pub fn known_until(&self, player: usize) -> u32 {
+        let known = self.inputs.iter().take_while(|row| row[player].is_some()).count();
+        self.base + known as u32
+    }
+
+    /// Whether the simulation has to wait for remote inputs before the next tick.
+    pub fn stalled(&self) -> bool {
+        self.tick - self.base >= MAX_PREDICTION
+    }
+
+    /// Set the input of a player at a tick, local or remote ones, in any order.
+    pub fn add_input(&mut self, player: usize, tick: u32, input: PlayerInput) {
+        if tick < self.base {
+            return;
+        }
+        let index = (tick - self.base) as usize;
+        while self.inputs.len() <= index {
+            self.inputs.push_back(vec![None; self.players]);
+        }
+        if self.inputs[index][player].is_some() {
+            return;
+        }
+        let predicted = self.input_at(player, tick);
+        self.inputs[index][player] = Some(input);
+        if tick < self.tick && predicted != input {
+            self.rewind_to = Some(self.rewind_to.map_or(tick, |rewind_to| rewind_to.min(tick)));
+        }
+        self.confirm();
+    }
+
+    /// Simulate the next tick with known and predicted inputs, after correcting wrong predictions.
+    /// Returns `None` without simulating if it's stalled.
+    pub fn advance(&mut self) -> Option<Snapshot> {
+        if self.stalled() {
+            return None;
+        }
+        self.rewind();
+        self.step();
+        self.confirm();
+        Some(self.simulation.snapshot())
+    }
+
+    /// Go back to the earliest tick with a wrong prediction and simulate until now again.
+    pub fn rewind(&mut self) {
+        let rewind_to = match self.rewind_to.take() {
+            Some(rewind_to) => rewind_to,
+            None => return,
+        };
+        let now = self.tick;
+        debug!("Rewinding {} ticks", now - rewind_to);
+        let index = (rewind_to - self.base) as usize;
+        self.simulation.restore(&self.states[index]);
+        self.states.truncate(index + 1);
+        self.tick = rewind_to;
+        while self.tick < now {
+            self.step();
+        }
+    }
+
+    fn step(&mut self) {
+        let inputs: Vec<PlayerInput> = (0..self.players)
+            .map(|player| self.input_at(player, self.tick))
+            .collect();
+        self.simulation.step(&inputs);
+        self.tick += 1;
+        self.states.push_back(self.simulation.save());
+    }
+
+    /// The input of the player at the tick, or the prediction of it.
+    fn input_at(&self, player: usize, tick: u32) -> PlayerInput {
+        let end = ((tick - self.base) as usize + 1).min(self.inputs.len());
+        self.inputs
+            .range(..end)
+            .rev()
+            .find_map(|row| row[player])
+            .unwrap_or(self.last_confirmed[player])
+    }
+
+    /// Forget ticks before the oldest missing input, they never rewind.
+    fn confirm(&mut self) {
+        while self.base < self.tick && self.rewind_to.map_or(true, |t| t > self.base) {
+            let row = match self.inputs.front() {
+                Some(row) if row.iter().all(Option::is_some) => row,
+                _ => break,
+            };
+            self.last_confirmed = row.iter().map(|input| input.unwrap()).collect();
+            self.inputs.pop_front();
+            self.states.pop_front();
+            self.base += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::game_scene::WallColliders;
+
+    use super::*;
+
+    fn scripted_input(player: usize, tick: u32) -> PlayerInput {
+        PlayerInput {
+            movement: (((tick / 40 + player as u32) % 3) as f32 - 1.0, 1.0),
+            fire: (tick + player as u32 * 7) % 30 < 3,
+        }
+    }
+
+    #[test]
+    fn late_inputs_are_predicted_then_corrected() {
+        let mut expected = Simulation::new(2, 3, None, WallColliders::Cuboids);
+        let mut rollback = Rollback::new(Simulation::new(2, 3, None, WallColliders::Cuboids));
+        const DELAY: u32 = 8;
+        for tick in 0..600 {
+            rollback.add_input(0, tick, scripted_input(0, tick));
+            if tick >= DELAY {
+                rollback.add_input(1, tick - DELAY, scripted_input(1, tick - DELAY));
+            }
+            assert!(rollback.advance().is_some());
+            assert!(rollback.tick() - rollback.base <= DELAY + 1);
+        }
+        for tick in 600 - DELAY..600 {
+            rollback.add_input(1, tick, scripted_input(1, tick));
+        }
+        rollback.rewind();
+
+        let mut snapshot = None;
+        for tick in 0..600 {
+            let inputs = [scripted_input(0, tick), scripted_input(1, tick)];
+            snapshot = Some(expected.step(&inputs));
+        }
+        assert_eq!(rollback.simulation().snapshot(), snapshot.unwrap());
+        assert_eq!(rollback.known_until(1), 600);
+    }
+
+    #[test]
+    fn stalls_without_remote_inputs() {
+        let mut rollback = Rollback::new(Simulation::new(2, 3, None, WallColliders::Cuboids));
+        for tick in 0..MAX_PREDICTION {
+            rollback.add_input(0, tick, PlayerInput::default());
+            assert!(rollback.advance().is_some());
+        }
+        assert!(rollback.stalled());
+        assert!(rollback.advance().is_none());
+        rollback.add_input(1, 0, PlayerInput::default());
+        assert!(!rollback.stalled());
+    }
+}
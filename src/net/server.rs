@@ -0,0 +1,506 @@
+//! The authoritative server, it runs without a window.
+use std::{
+    error::Error,
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    ops::Range,
+    thread, time,
+};
+
+use crossbeam_channel::tick;
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled, warn};
+
+use crate::input::{Controller, network_controller::NetworkController};
+use crate::options::Options;
+use crate::scene::{
+    game_scene::{replay::Replay, simulation::Simulation, PHYSICAL_DT},
+    palette::{self, MAX_PLAYERS},
+};
+
+use super::{
+    protocol::{
+        self, ClientMessage, GameInfo, LobbyState, Seat, ServerMessage, MAX_PACKET_SIZE,
+        PROTOCOL_VERSION,
+    },
+    quality::DISCONNECT_TIMEOUT,
+};
+
+/// Time between two lobby states sent to clients.
+const LOBBY_INTERVAL: time::Duration = time::Duration::from_millis(50);
+/// Ticks between two logs of the connection quality.
+const QUALITY_LOG_INTERVAL: u32 = (5.0 / PHYSICAL_DT) as u32;
+
+pub struct Server {
+    socket: UdpSocket,
+    /// Tells the games on the LAN apart.
+    id: u64,
+    /// The most players the lobby takes.
+    places: usize,
+    /// Number of players in the match, known when it starts.
+    players: usize,
+    started: bool,
+    /// The first client is the host.
+    clients: Vec<RemoteClient>,
+    /// Players of the match, fed by the inputs from their clients.
+    controllers: Vec<NetworkController>,
+    buffer: Vec<u8>,
+}
+
+struct RemoteClient {
+    address: SocketAddr,
+    /// Index of its first player in the match.
+    first: usize,
+    /// Its players in the lobby.
+    seats: Vec<Seat>,
+    /// The round whose maze the client has.
+    round: u32,
+    /// When the last message came, silent clients are removed from the lobby.
+    last_seen: time::Instant,
+}
+
+/// Run a match for the clients as the options say, until all of them leave.
+pub fn serve(address: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let server = Server::bind(address, options.places.unwrap_or(MAX_PLAYERS))?;
+    info!("Server listening on {}, {} places in the lobby", server.local_addr()?, server.places);
+    host(server, options)
+}
+
+/// Run the server in another thread of this process, returns the address to join it from here.
+pub fn spawn(address: &str, options: &Options) -> io::Result<SocketAddr> {
+    let server = Server::bind(address, options.places.unwrap_or(MAX_PLAYERS))?;
+    let mut local_addr = server.local_addr()?;
+    info!("Hosting on {}", local_addr);
+    if local_addr.ip().is_unspecified() {
+        local_addr.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+    let options = options.clone();
+    thread::spawn(move || host(server, &options).unwrap_or_else(|err| error!("{}", err)));
+    Ok(local_addr)
+}
+
+fn host(mut server: Server, options: &Options) -> Result<(), Box<dyn Error>> {
+    server.lobby()?;
+    let players = server.players;
+    let seed = options.seed.unwrap_or_else(rand::random);
+    info!("Starting the match of {} players with seed {}", players, seed);
+    let mut simulation = Simulation::new(players, seed, options.arena.clone(), options.colliders);
+    let mut replay = options
+        .record
+        .as_ref()
+        .map(|_| Replay::new(players, seed, options.arena.clone(), options.colliders));
+    server.run(&mut simulation, replay.as_mut())?;
+
+    if let (Some(replay), Some(path)) = (replay, &options.record) {
+        replay.save(path)?;
+        info!("Replay of {} ticks is saved to {}", replay.ticks(), path.display());
+    }
+    Ok(())
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs, places: usize) -> io::Result<Server> {
+        Ok(Server {
+            socket: UdpSocket::bind(address)?,
+            id: rand::random(),
+            places,
+            players: 0,
+            started: false,
+            clients: Vec::new(),
+            controllers: Vec::new(),
+            buffer: vec![0; MAX_PACKET_SIZE],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Keep the lobby until the host starts the match, the players are the seats taken then.
+    pub fn lobby(&mut self) -> io::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        let ticker = tick(LOBBY_INTERVAL);
+        loop {
+            // The ticker never disconnects.
+            ticker.recv().unwrap();
+            let start = self.receive_lobby()?;
+            self.remove_silent();
+            if start {
+                break;
+            }
+            self.send_lobby()?;
+        }
+        // Clients without players can't take part in the match.
+        let (clients, empty) = self.clients.drain(..).partition(|c| !c.seats.is_empty());
+        self.clients = clients;
+        for client in empty {
+            let refused = ServerMessage::Refused("The match started without you".to_string());
+            self.send_message(&refused, client.address)?;
+        }
+        self.players = self.joined();
+        self.started = true;
+        Ok(())
+    }
+
+    /// Run the match until all clients leave, the inputs of every tick are recorded to the replay.
+    pub fn run(
+        &mut self,
+        simulation: &mut Simulation,
+        mut replay: Option<&mut Replay>,
+    ) -> io::Result<()> {
+        self.socket.set_nonblocking(true)?;
+        self.controllers = (0..self.players).map(|_| NetworkController::new()).collect();
+        let controllers: Vec<Box<dyn Controller>> = self
+            .controllers
+            .iter()
+            .map(|controller| -> Box<dyn Controller> { Box::new(controller.clone()) })
+            .collect();
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        let mut round = 0;
+        let mut maze = Vec::new();
+        loop {
+            // The ticker never disconnects.
+            ticker.recv().unwrap();
+            self.receive()?;
+            self.remove_disconnected();
+            if self.clients.is_empty() {
+                info!("All clients left, the match is over");
+                return Ok(());
+            }
+
+            // Players of disconnected clients stay in the match without moving.
+            let inputs = simulation.poll_inputs(&controllers);
+            let snapshot = simulation.step(&inputs);
+            if let Some(replay) = replay.as_mut() {
+                replay.record(&inputs);
+            }
+
+            if snapshot.seq_number % QUALITY_LOG_INTERVAL == 0 {
+                self.log_quality();
+            }
+            if snapshot.round != round {
+                round = snapshot.round;
+                maze = protocol::encode(&ServerMessage::Maze {
+                    round,
+                    maze: simulation.maze().clone(),
+                });
+            }
+            let snapshot = protocol::encode(&ServerMessage::Snapshot(snapshot));
+            for client in self.clients.iter() {
+                if client.round != round {
+                    self.send(&maze, client.address)?;
+                }
+                self.send(&snapshot, client.address)?;
+            }
+        }
+    }
+
+    fn joined(&self) -> usize {
+        self.clients.iter().map(|client| client.seats.len()).sum()
+    }
+
+    /// Handle all datagrams received in the lobby, returns true if the host starts the match.
+    fn receive_lobby(&mut self) -> io::Result<bool> {
+        let mut start = false;
+        loop {
+            let (len, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(start),
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err),
+            };
+            if let Some(client) = self.clients.iter_mut().find(|c| c.address == address) {
+                client.last_seen = time::Instant::now();
+            }
+            match protocol::decode(&self.buffer[..len]) {
+                Some(ClientMessage::Discover { version }) => self.answer(address, version)?,
+                Some(ClientMessage::Join { version }) => self.join(address, version)?,
+                Some(ClientMessage::Seats(seats)) => self.seat(address, seats),
+                Some(ClientMessage::Start) => {
+                    let host = self.clients.first().map(|c| c.address);
+                    if host == Some(address) && self.everyone_ready() {
+                        start = true;
+                    }
+                }
+                Some(ClientMessage::Leave) => self.leave(address),
+                Some(ClientMessage::Input { .. }) | None => {}
+            }
+        }
+    }
+
+    /// At least two players are in the lobby and all of them are ready.
+    fn everyone_ready(&self) -> bool {
+        let mut seats = self.clients.iter().flat_map(|client| client.seats.iter());
+        self.joined() >= 2 && seats.all(|seat| seat.ready)
+    }
+
+    /// Change the players of the client, they get free colours and places only.
+    fn seat(&mut self, address: SocketAddr, seats: Vec<Seat>) {
+        let index = match self.clients.iter().position(|c| c.address == address) {
+            Some(index) => index,
+            None => return,
+        };
+        let mut taken: Vec<usize> = self
+            .clients
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .flat_map(|(_, client)| client.seats.iter().map(|seat| seat.colour))
+            .collect();
+        let free = self.places.saturating_sub(taken.len());
+        let old = &self.clients[index].seats;
+        let mut new = Vec::new();
+        for (i, seat) in seats.into_iter().take(free).enumerate() {
+            // Someone else has just taken the colour, keep the old one.
+            let wanted = match old.get(i) {
+                Some(old) if taken.contains(&seat.colour) => old.colour,
+                _ => seat.colour,
+            };
+            let colour = palette::free_colour(&taken, wanted);
+            taken.push(colour);
+            new.push(Seat {
+                colour,
+                ready: seat.ready,
+            });
+        }
+        if new.len() != old.len() {
+            info!("{} has {} players in the lobby", address, new.len());
+        }
+        self.clients[index].seats = new;
+        self.reseat();
+    }
+
+    /// Number the players again after the players of a client change.
+    fn reseat(&mut self) {
+        let mut first = 0;
+        for client in self.clients.iter_mut() {
+            client.first = first;
+            first += client.seats.len();
+        }
+    }
+
+    fn send_lobby(&self) -> io::Result<()> {
+        let seats: Vec<Seat> = self
+            .clients
+            .iter()
+            .flat_map(|client| client.seats.iter().copied())
+            .collect();
+        for (i, client) in self.clients.iter().enumerate() {
+            let lobby = ServerMessage::Lobby(LobbyState {
+                seats: seats.clone(),
+                first: client.first,
+                host: i == 0,
+            });
+            self.send_message(&lobby, client.address)?;
+        }
+        Ok(())
+    }
+
+    /// Remove clients which stopped talking in the lobby.
+    fn remove_silent(&mut self) {
+        let count = self.clients.len();
+        self.clients.retain(|client| {
+            let silent = client.last_seen.elapsed() >= DISCONNECT_TIMEOUT;
+            if silent {
+                info!("{} is disconnected", client.address);
+            }
+            !silent
+        });
+        if count != self.clients.len() {
+            self.reseat();
+        }
+    }
+
+    /// Tell a browser about this game.
+    fn answer(&self, address: SocketAddr, version: u16) -> io::Result<()> {
+        if version != PROTOCOL_VERSION {
+            return Ok(());
+        }
+        let game = ServerMessage::Game(GameInfo {
+            id: self.id,
+            players: self.joined(),
+            places: self.places,
+            started: self.started,
+        });
+        self.send_message(&game, address)
+    }
+
+    /// Handle all datagrams received since last tick.
+    fn receive(&mut self) -> io::Result<()> {
+        loop {
+            let (len, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err),
+            };
+            match protocol::decode(&self.buffer[..len]) {
+                Some(ClientMessage::Input {
+                    sequence,
+                    round,
+                    inputs,
+                }) => {
+                    if let Some(client) = self.clients.iter_mut().find(|c| c.address == address) {
+                        // A late message may carry an older round.
+                        client.round = client.round.max(round);
+                        let now = time::Instant::now();
+                        let controllers = &self.controllers[client.players()];
+                        for (controller, input) in controllers.iter().zip(inputs) {
+                            controller.receive(sequence, input, now);
+                        }
+                    }
+                }
+                Some(ClientMessage::Discover { version }) => self.answer(address, version)?,
+                Some(ClientMessage::Join { version }) => self.join(address, version)?,
+                Some(ClientMessage::Leave) => {
+                    if let Some(client) = self.clients.iter().find(|c| c.address == address) {
+                        let controllers = &self.controllers[client.players()];
+                        controllers.iter().for_each(NetworkController::disconnect);
+                    }
+                }
+                Some(ClientMessage::Seats(_)) | Some(ClientMessage::Start) | None => {}
+            }
+        }
+    }
+
+    /// Remove clients which left or stopped sending inputs.
+    fn remove_disconnected(&mut self) {
+        let controllers = &self.controllers;
+        self.clients.retain(|client| {
+            let connected = controllers[client.first].quality().connected;
+            if !connected {
+                info!("{} is disconnected", client.address);
+            }
+            connected
+        });
+    }
+
+    fn log_quality(&self) {
+        for client in self.clients.iter() {
+            let quality = self.controllers[client.first].quality();
+            info!("Connection to {}: {}", client.address, quality);
+        }
+    }
+
+    fn join(&mut self, address: SocketAddr, version: u16) -> io::Result<()> {
+        // The welcome message was lost.
+        if self.clients.iter().any(|client| client.address == address) {
+            return self.send_message(&ServerMessage::Welcome, address);
+        }
+        let reason = if version != PROTOCOL_VERSION {
+            format!("The server uses protocol {}", PROTOCOL_VERSION)
+        } else if self.started {
+            "The match has started".to_string()
+        } else {
+            if self.clients.is_empty() {
+                info!("{} joined the lobby as the host", address);
+            } else {
+                info!("{} joined the lobby", address);
+            }
+            self.clients.push(RemoteClient {
+                address,
+                first: self.joined(),
+                seats: Vec::new(),
+                round: 0,
+                last_seen: time::Instant::now(),
+            });
+            return self.send_message(&ServerMessage::Welcome, address);
+        };
+        warn!("{} is refused: {}", address, reason);
+        self.send_message(&ServerMessage::Refused(reason), address)
+    }
+
+    /// Remove the client before the match starts, players after it move forward.
+    fn leave(&mut self, address: SocketAddr) {
+        if let Some(index) = self.clients.iter().position(|c| c.address == address) {
+            let client = self.clients.remove(index);
+            info!("{} left", client.address);
+            self.reseat();
+        }
+    }
+
+    fn send_message(&self, message: &ServerMessage, address: SocketAddr) -> io::Result<()> {
+        self.send(&protocol::encode(message), address)
+    }
+
+    fn send(&self, data: &[u8], address: SocketAddr) -> io::Result<()> {
+        match self.socket.send_to(data, address) {
+            Ok(_) => Ok(()),
+            // Datagrams may be lost anyway, the client will ask again.
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl RemoteClient {
+    /// Indices of its players in the match.
+    fn players(&self) -> Range<usize> {
+        self.first..self.first + self.seats.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{client::Client, discovery::Browser};
+    use super::*;
+
+    /// Wait for a lobby state which the condition holds for.
+    fn wait_lobby(client: &mut Client, condition: impl Fn(&LobbyState) -> bool) -> LobbyState {
+        let deadline = time::Instant::now() + time::Duration::from_secs(5);
+        while time::Instant::now() < deadline {
+            for message in client.receive().unwrap() {
+                if let ServerMessage::Lobby(state) = message {
+                    if condition(&state) {
+                        return state;
+                    }
+                }
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        panic!("The lobby never gets there");
+    }
+
+    #[test]
+    fn host_starts_when_everyone_is_ready() {
+        let mut server = Server::bind("127.0.0.1:0", 3).unwrap();
+        let address = server.local_addr().unwrap();
+        let lobby = thread::spawn(move || {
+            server.lobby().unwrap();
+            server
+        });
+        let seat = |colour, ready| Seat { colour, ready };
+
+        let mut host = Client::connect(address).unwrap();
+        let mut guest = Client::connect(address).unwrap();
+        host.send_seats(vec![seat(0, false), seat(1, false)]).unwrap();
+        wait_lobby(&mut host, |state| state.seats.len() == 2);
+        // Only one place is left, and the colour is taken.
+        guest.send_seats(vec![seat(1, true), seat(3, true)]).unwrap();
+        let state = wait_lobby(&mut guest, |state| state.seats.len() == 3);
+        assert_eq!(state.first, 2);
+        assert!(!state.host);
+        assert_eq!(state.seats[2], seat(2, true));
+        assert_eq!(guest.first_player(), 2);
+
+        let mut browser = Browser::new(address.port()).unwrap();
+        let deadline = time::Instant::now() + time::Duration::from_secs(5);
+        while browser.games().is_empty() {
+            assert!(time::Instant::now() < deadline, "The game is never found");
+            browser.update().unwrap();
+            thread::sleep(time::Duration::from_millis(10));
+        }
+        let game = &browser.games()[0].info;
+        assert_eq!((game.players, game.places, game.started), (3, 3, false));
+
+        // Not everyone is ready, and only the host can start.
+        host.start().unwrap();
+        guest.start().unwrap();
+        host.send_seats(vec![seat(0, true), seat(1, true)]).unwrap();
+        let state = wait_lobby(&mut host, |state| state.seats.iter().all(|seat| seat.ready));
+        assert!(state.host);
+        host.start().unwrap();
+        let server = lobby.join().unwrap();
+        assert_eq!(server.players, 3);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{error::Error, fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+
+use crate::scene::{
+    game_scene::{replay::Replay, WallColliders},
+    maze::{self, Maze},
+    palette::MAX_PLAYERS,
+};
+
+/// Options given from the command line.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Seed of the match, mazes of every round are generated from it.
+    /// A random one is used if it's not specified.
+    pub seed: Option<u64>,
+    /// Play on this maze in every round, instead of random ones.
+    /// When editing, this is the maze loaded from the file.
+    pub arena: Option<Maze>,
+    /// Open the maze editor for this file instead of playing.
+    pub edit: Option<PathBuf>,
+    /// Shapes of the maze in the physic world.
+    pub colliders: WallColliders,
+    /// Save the replay of the match to this file.
+    pub record: Option<PathBuf>,
+    /// Watch this replay instead of playing.
+    pub replay: Option<Replay>,
+    /// Run a LAN server on this address without a window.
+    pub server: Option<String>,
+    /// Run a LAN server on this address in this process, and join its lobby.
+    pub host: Option<String>,
+    /// Players needed to start a local match.
+    pub players: Option<usize>,
+    /// The most players in the lobby of the server.
+    pub places: Option<usize>,
+    /// Join the lobby of this server instead of playing locally.
+    pub connect: Option<SocketAddr>,
+    /// List the games on the LAN to join one.
+    pub browse: bool,
+    /// The settings file of key bindings, instead of the default one.
+    pub bindings: Option<PathBuf>,
+    /// Play without a server, with the peers on these addresses, one player each.
+    pub peers: Option<Vec<SocketAddr>>,
+    /// Index of the local player in `peers`.
+    pub player: usize,
+}
+
+#[derive(Debug)]
+pub struct OptionsError(String);
+
+impl Options {
+    pub fn from_args() -> Result<Options, OptionsError> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, OptionsError> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => options.seed = Some(parse_value(&arg, args.next())?),
+                "--colliders" => options.colliders = parse_value(&arg, args.next())?,
+                "--maze" => {
+                    let path: PathBuf = parse_value(&arg, args.next())?;
+                    let arena = maze::file::load(&path).map_err(|err| {
+                        OptionsError(format!("Invalid maze file {}: {}", path.display(), err))
+                    })?;
+                    options.arena = Some(arena);
+                }
+                "--server" => options.server = Some(parse_value(&arg, args.next())?),
+                "--host" => options.host = Some(parse_value(&arg, args.next())?),
+                "--connect" => options.connect = Some(parse_value(&arg, args.next())?),
+                "--browse" => options.browse = true,
+                "--players" => match parse_value(&arg, args.next())? {
+                    0 | 1 => {
+                        return Err(OptionsError("A match needs at least 2 players".to_string()));
+                    }
+                    players if players > MAX_PLAYERS => {
+                        let message = format!("A match has {} players at most", MAX_PLAYERS);
+                        return Err(OptionsError(message));
+                    }
+                    players => options.players = Some(players),
+                },
+                "--places" => match parse_value(&arg, args.next())? {
+                    0 => return Err(OptionsError("A match needs at least 1 player".to_string())),
+                    places if places > MAX_PLAYERS => {
+                        let message = format!("A match has {} players at most", MAX_PLAYERS);
+                        return Err(OptionsError(message));
+                    }
+                    places => options.places = Some(places),
+                },
+                "--peers" => {
+                    let peers: String = parse_value(&arg, args.next())?;
+                    let peers = peers
+                        .split(',')
+                        .map(|peer| parse_value(&arg, Some(peer.to_string())))
+                        .collect::<Result<_, _>>()?;
+                    options.peers = Some(peers);
+                }
+                "--player" => options.player = parse_value(&arg, args.next())?,
+                "--bindings" => options.bindings = Some(parse_value(&arg, args.next())?),
+                "--record" => options.record = Some(parse_value(&arg, args.next())?),
+                "--replay" => {
+                    let path: PathBuf = parse_value(&arg, args.next())?;
+                    let replay = Replay::load(&path).map_err(|err| {
+                        OptionsError(format!("Invalid replay file {}: {}", path.display(), err))
+                    })?;
+                    options.replay = Some(replay);
+                }
+                "--edit" => {
+                    let path: PathBuf = parse_value(&arg, args.next())?;
+                    if path.exists() {
+                        let arena = maze::file::load(&path).map_err(|err| {
+                            OptionsError(format!("Invalid maze file {}: {}", path.display(), err))
+                        })?;
+                        options.arena = Some(arena);
+                    }
+                    options.edit = Some(path);
+                }
+                _ => return Err(OptionsError(format!("Unknown option: {}", arg))),
+            }
+        }
+        if let Some(peers) = &options.peers {
+            if peers.len() < 2 {
+                return Err(OptionsError("A match needs at least 2 peers".to_string()));
+            }
+            if options.player >= peers.len() {
+                return Err(OptionsError(format!("There is no player {}", options.player)));
+            }
+            // Every peer simulates the match, they must agree on the seed.
+            if options.seed.is_none() {
+                return Err(OptionsError("Peers need the same --seed".to_string()));
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<String>) -> Result<T, OptionsError> {
+    let value = value.ok_or_else(|| OptionsError(format!("Missing value of {}", name)))?;
+    value
+        .parse()
+        .map_err(|_| OptionsError(format!("Invalid value of {}: {}", name, value)))
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for OptionsError {}
@@ -0,0 +1,152 @@
+//! A match running on a LAN server, drawn by the render of the game scene.
+//! Clients come here from the lobby when the match starts.
+use std::{cell::RefCell, error::Error, time};
+
+use crossbeam_channel::tick;
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+
+use crate::input::{Controller, input_center::InputCenter, PlayerInput, WorldView};
+use crate::net::{client::Client, protocol::ServerMessage, quality::LinkMonitor};
+
+use super::{
+    game_scene::{
+        self, maze_layer::MazeData, simulation::Snapshot, FrameData, GameSceneRender,
+        RenderChannels, PHYSICAL_DT,
+    },
+    lobby_scene,
+    maze::Maze,
+    SceneRender, SceneUpdater,
+};
+
+/// Ticks between two logs of the connection quality.
+const QUALITY_LOG_INTERVAL: u32 = (5.0 / PHYSICAL_DT) as u32;
+
+pub struct ClientSceneUpdater {
+    client: RefCell<Client>,
+    controllers: Vec<Box<dyn Controller>>,
+    /// Colours of all players picked in the lobby.
+    colours: Vec<usize>,
+    channels: RenderChannels,
+}
+
+pub(crate) fn new(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    client: Client,
+    controllers: Vec<Box<dyn Controller>>,
+    colours: Vec<usize>,
+) -> (GameSceneRender, ClientSceneUpdater) {
+    let (render, channels) = game_scene::render(device, format);
+    (
+        render,
+        ClientSceneUpdater {
+            client: RefCell::new(client),
+            controllers,
+            colours,
+            channels,
+        },
+    )
+}
+
+impl ClientSceneUpdater {
+    fn manage(&self, input_center: &InputCenter) -> Result<(), Box<dyn Error>> {
+        let client = &mut *self.client.borrow_mut();
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        // The maze of the round, and the latest snapshot from the server.
+        let mut maze: Option<(u32, Maze)> = None;
+        let mut snapshot: Option<Snapshot> = None;
+        let mut monitor = LinkMonitor::new(time::Instant::now());
+        let mut ticks = 0u32;
+
+        loop {
+            input_center.update();
+            let now = time::Instant::now();
+            let mut changed = false;
+            for message in client.receive()? {
+                match message {
+                    ServerMessage::Maze { round, maze: new } => {
+                        if maze.as_ref().map_or(true, |(old, _)| *old != round) {
+                            self.channels.maze_update_sender.send(MazeData::from(&new))?;
+                            maze = Some((round, new));
+                        }
+                    }
+                    // Snapshots arriving late are dropped.
+                    ServerMessage::Snapshot(new) => {
+                        if monitor.arrive(new.seq_number, now) {
+                            snapshot = Some(new);
+                            changed = true;
+                        }
+                    }
+                    ServerMessage::Game(_)
+                    | ServerMessage::Welcome
+                    | ServerMessage::Refused(_)
+                    | ServerMessage::Lobby(_) => {}
+                }
+            }
+            // The server says nothing before all players join.
+            let quality = monitor.quality(now);
+            if snapshot.is_some() && !quality.connected {
+                return Err("The server stopped answering".into());
+            }
+            ticks += 1;
+            if ticks % QUALITY_LOG_INTERVAL == 0 {
+                debug!("Connection to the server: {}", quality);
+            }
+
+            let round = maze.as_ref().map_or(0, |(round, _)| *round);
+            let inputs = self.poll_inputs(client, maze.as_ref(), snapshot.as_ref());
+            client.send_inputs(round, inputs)?;
+
+            let frame = if changed {
+                snapshot
+                    .as_ref()
+                    .map(|snapshot| FrameData::new(snapshot, &self.colours))
+            } else {
+                None
+            };
+            if !self.channels.wait_tick(&ticker, frame)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Let local controllers see the world from the latest snapshot, then collect their input.
+    fn poll_inputs(
+        &self,
+        client: &Client,
+        maze: Option<&(u32, Maze)>,
+        snapshot: Option<&Snapshot>,
+    ) -> Vec<PlayerInput> {
+        if let (Some((_, maze)), Some(snapshot)) = (maze, snapshot) {
+            let tanks = snapshot.tank_poses();
+            for (i, controller) in self.controllers.iter().enumerate() {
+                let me = client.first_player() + i;
+                if me < tanks.len() {
+                    controller.observe(&WorldView {
+                        maze,
+                        tanks: &tanks,
+                        me,
+                    });
+                }
+            }
+        }
+        self.controllers.iter().map(|c| c.input()).collect()
+    }
+}
+
+impl SceneUpdater for ClientSceneUpdater {
+    fn update(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        input_center: &InputCenter,
+    ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
+        // The window is closing if it stops without an error,
+        // otherwise look for another game on the LAN as the server is gone.
+        let err = self.manage(input_center).err()?;
+        error!("{}", err);
+        let (render, updater) = lobby_scene::new(device, format, None, Some(err.to_string()));
+        Some((Box::new(render), Box::new(updater)))
+    }
+}
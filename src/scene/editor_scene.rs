@@ -0,0 +1,380 @@
+//! A scene to draw mazes by hand and save them for `--maze`.
+//!
+//! Mouse: click near a wall to toggle it.
+//! Keyboard: arrows move the selected cell, `W` `A` `S` `D` toggle its walls,
+//! `[` `]` change the width, `-` `=` change the height, `C` checks the connectivity
+//! and `Enter` saves the maze.
+use std::{cmp::Ordering, error::Error, path::PathBuf, time};
+
+use crossbeam_channel::{bounded, Receiver, select, Sender, tick, unbounded};
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled, warn};
+use rapier2d::{math::Point, na::Vector4};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::input::input_center::{EventKind, InputCenter, PointerEvent};
+
+use super::{
+    game_scene::{
+        bullet_layer::{BulletInstance, BulletLayer},
+        camera::{self, Camera},
+        maze_layer::{MazeData, MazeLayer},
+        Vertex,
+    },
+    maze::{self, Cell, Direction, Maze},
+    render_layer::Layer,
+    SceneRender, SceneUpdater,
+};
+
+const DEFAULT_SIZE: (usize, usize) = (8, 6);
+const MAX_SIZE: usize = 30;
+const EDITOR_DT: f32 = 1.0 / 60.0;
+
+pub struct EditorSceneRender {
+    clean_color: wgpu::Color,
+
+    camera: Camera,
+
+    maze_layer: MazeLayer,
+    /// Cells which can't be reached from the rest of the maze.
+    unreachable_layer: MazeLayer,
+    cursor_layer: BulletLayer,
+
+    maze_size: [usize; 2],
+
+    frame_update_chan: Receiver<EditorFrame>,
+    stop_signal_sender: Sender<()>,
+}
+
+pub struct EditorSceneUpdater {
+    path: PathBuf,
+    maze: Maze,
+
+    frame_update_sender: Sender<EditorFrame>,
+    stop_signal_chan: Receiver<()>,
+}
+
+struct EditorFrame {
+    maze: MazeData,
+    unreachable: MazeData,
+    cursor: Vec<BulletInstance>,
+}
+
+/// What the editor is pointing at.
+struct Selection {
+    /// The cell selected by the keyboard.
+    cell: Cell,
+    /// The wall under the mouse.
+    hovered: Option<(Cell, Direction)>,
+    /// Last mouse position and frame size, the hovered wall changes when the maze is resized.
+    pointer: Option<([f32; 2], [f32; 2])>,
+}
+
+pub(crate) fn new(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    path: PathBuf,
+    maze: Option<Maze>,
+) -> (EditorSceneRender, EditorSceneUpdater) {
+    info!("Editing maze {}", path.display());
+    let clean_color = wgpu::Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+
+    let camera = Camera::new(device);
+    let uniform_bind_group_layout = &camera.uniform_bind_group_layout;
+    let maze_layer = MazeLayer::new(device, format.into(), uniform_bind_group_layout);
+    let unreachable_layer = MazeLayer::new(device, format.into(), uniform_bind_group_layout);
+    let cursor_layer = BulletLayer::new(device, format.into(), uniform_bind_group_layout);
+
+    let (frame_update_sender, frame_update_chan) = unbounded();
+    let (stop_signal_sender, stop_signal_chan) = bounded(0);
+
+    (
+        EditorSceneRender {
+            clean_color,
+            camera,
+            maze_layer,
+            unreachable_layer,
+            cursor_layer,
+            maze_size: [1, 1],
+            frame_update_chan,
+            stop_signal_sender,
+        },
+        EditorSceneUpdater {
+            path,
+            maze: maze.unwrap_or_else(|| Maze::empty(DEFAULT_SIZE.0, DEFAULT_SIZE.1)),
+            frame_update_sender,
+            stop_signal_chan,
+        },
+    )
+}
+
+impl EditorSceneUpdater {
+    fn manage(&self, input_center: &InputCenter) -> Result<(), Box<dyn Error>> {
+        let ticker = tick(time::Duration::from_secs_f32(EDITOR_DT));
+        let mut maze = self.maze.clone();
+        let mut selection = Selection {
+            cell: (0, 0),
+            hovered: None,
+            pointer: None,
+        };
+        self.send_frame(&maze, &selection)?;
+
+        loop {
+            select! {
+                recv(self.stop_signal_chan) -> _ => return Ok(()),
+                recv(ticker) -> _ => {}
+            }
+
+            let batch = input_center.update();
+            let mut changed = false;
+            for key in batch.pressed_keys() {
+                changed |= self.handle_key(&mut maze, &mut selection, key);
+            }
+            for event in batch.events.iter() {
+                let event = match event.kind {
+                    EventKind::Pointer(event) => event,
+                    _ => continue,
+                };
+                match event {
+                    PointerEvent::Moved {
+                        position,
+                        frame_size,
+                    } => selection.pointer = Some((position, frame_size)),
+                    PointerEvent::Pressed(MouseButton::Left) => {
+                        if let Some((cell, direction)) = selection.hovered {
+                            toggle_wall(&mut maze, cell, direction);
+                        }
+                    }
+                    PointerEvent::Pressed(_) => continue,
+                }
+                changed = true;
+            }
+
+            if changed {
+                selection.cell.0 = selection.cell.0.min(maze.width - 1);
+                selection.cell.1 = selection.cell.1.min(maze.height - 1);
+                selection.hovered = selection
+                    .pointer
+                    .and_then(|(position, frame_size)| pick_wall(&maze, position, frame_size));
+                self.send_frame(&maze, &selection)?;
+            }
+        }
+    }
+
+    /// Returns whether anything is changed.
+    fn handle_key(&self, maze: &mut Maze, selection: &mut Selection, key: VirtualKeyCode) -> bool {
+        let (width, height) = (maze.width, maze.height);
+        let cell = selection.cell;
+        match key {
+            VirtualKeyCode::Up => selection.cell.1 = (cell.1 + 1).min(height - 1),
+            VirtualKeyCode::Down => selection.cell.1 = cell.1.saturating_sub(1),
+            VirtualKeyCode::Left => selection.cell.0 = cell.0.saturating_sub(1),
+            VirtualKeyCode::Right => selection.cell.0 = (cell.0 + 1).min(width - 1),
+            VirtualKeyCode::W => toggle_wall(maze, cell, Direction::Top),
+            VirtualKeyCode::A => toggle_wall(maze, cell, Direction::Left),
+            VirtualKeyCode::S => toggle_wall(maze, cell, Direction::Bottom),
+            VirtualKeyCode::D => toggle_wall(maze, cell, Direction::Right),
+            VirtualKeyCode::LBracket if width > 1 => *maze = maze.resized(width - 1, height),
+            VirtualKeyCode::RBracket if width < MAX_SIZE => *maze = maze.resized(width + 1, height),
+            VirtualKeyCode::Minus if height > 1 => *maze = maze.resized(width, height - 1),
+            VirtualKeyCode::Equals if height < MAX_SIZE => *maze = maze.resized(width, height + 1),
+            VirtualKeyCode::C => {
+                if maze.is_connected() {
+                    info!("All cells of the maze are connected");
+                } else {
+                    warn!("Some cells can't be reached, they are filled in the editor");
+                }
+                return false;
+            }
+            VirtualKeyCode::Return => {
+                if !maze.is_connected() {
+                    warn!("Saving a maze whose cells are not all connected");
+                }
+                match maze::file::save(maze, &self.path) {
+                    Ok(()) => info!("Maze saved to {}", self.path.display()),
+                    Err(err) => error!("Failed to save {}: {}", self.path.display(), err),
+                }
+                return false;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn send_frame(&self, maze: &Maze, selection: &Selection) -> Result<(), Box<dyn Error>> {
+        let size = [maze.width, maze.height];
+
+        let mut marks = MazeData {
+            vertex: Vec::new(),
+            index: Vec::new(),
+            size,
+        };
+        for (x, y) in unreachable_cells(maze) {
+            const HALF_SIZE: f32 = 0.3;
+            let center = maze.cell_center((x, y));
+            let (cx, cy) = (center.x, center.y);
+            let offset = marks.vertex.len() as u32;
+            marks.vertex.extend_from_slice(&[
+                Vertex::new(cx - HALF_SIZE, cy - HALF_SIZE),
+                Vertex::new(cx + HALF_SIZE, cy - HALF_SIZE),
+                Vertex::new(cx + HALF_SIZE, cy + HALF_SIZE),
+                Vertex::new(cx - HALF_SIZE, cy + HALF_SIZE),
+            ]);
+            marks
+                .index
+                .extend([0, 1, 2, 0, 2, 3].iter().map(|i| offset + i));
+        }
+
+        // A ring of dots around the selected cell, and a dot on the hovered wall.
+        const RING_DOTS: usize = 8;
+        const RING_RADIUS: f32 = 0.25;
+        let center = maze.cell_center(selection.cell);
+        let mut cursor: Vec<BulletInstance> = (0..RING_DOTS)
+            .map(|i| {
+                let angle = i as f32 / RING_DOTS as f32 * std::f32::consts::TAU;
+                BulletInstance {
+                    position: [
+                        center.x + RING_RADIUS * angle.cos(),
+                        center.y + RING_RADIUS * angle.sin(),
+                    ],
+                    velocity: [0.0, 0.0],
+                }
+            })
+            .collect();
+        if let Some((cell, direction)) = selection.hovered {
+            let center = maze.cell_center(cell);
+            let (dx, dy) = match direction {
+                Direction::Top => (0.0, 0.5),
+                Direction::Right => (0.5, 0.0),
+                Direction::Bottom => (0.0, -0.5),
+                Direction::Left => (-0.5, 0.0),
+            };
+            cursor.push(BulletInstance {
+                position: [center.x + dx, center.y + dy],
+                velocity: [0.0, 0.0],
+            });
+        }
+
+        self.frame_update_sender.send(EditorFrame {
+            maze: MazeData::from(maze),
+            unreachable: marks,
+            cursor,
+        })?;
+        Ok(())
+    }
+}
+
+fn toggle_wall(maze: &mut Maze, cell: Cell, direction: Direction) {
+    let wall = maze.has_wall(cell, direction);
+    maze.set_wall(cell, direction, !wall);
+}
+
+/// Find the inner wall nearest to the mouse position.
+fn pick_wall(maze: &Maze, position: [f32; 2], frame_size: [f32; 2]) -> Option<(Cell, Direction)> {
+    // The window is minimized, the projection would be full of NaN.
+    if frame_size[0] == 0.0 || frame_size[1] == 0.0 {
+        return None;
+    }
+    let inverse = camera::projection(&frame_size, &[maze.width, maze.height]).try_inverse()?;
+    let ndc = Vector4::new(
+        position[0] / frame_size[0] * 2.0 - 1.0,
+        1.0 - position[1] / frame_size[1] * 2.0,
+        0.0,
+        1.0,
+    );
+    let world = inverse * ndc;
+    let point = Point::new(world.x, world.y);
+    let cell = maze.cell_at(&point)?;
+    // Position inside the cell, from 0 to 1
+    let center = maze.cell_center(cell);
+    let (u, v) = (point.x - center.x + 0.5, point.y - center.y + 0.5);
+    let distances = [
+        (Direction::Top, 1.0 - v),
+        (Direction::Right, 1.0 - u),
+        (Direction::Bottom, v),
+        (Direction::Left, u),
+    ];
+    distances
+        .iter()
+        .filter(|&&(direction, _)| maze.neighbour(cell, direction).is_some())
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+        .map(|&(direction, _)| (cell, direction))
+}
+
+/// Cells outside the largest connected area of the maze.
+fn unreachable_cells(maze: &Maze) -> Vec<Cell> {
+    let mut areas = maze.areas();
+    areas.sort_by_key(Vec::len);
+    areas.pop();
+    areas.concat()
+}
+
+impl SceneRender for EditorSceneRender {
+    fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frame: &wgpu::SwapChainTexture,
+        frame_size: [u32; 2],
+    ) -> Result<(), wgpu::SwapChainError> {
+        // Only the latest edit is needed
+        if let Some(frame) = self.frame_update_chan.try_iter().last() {
+            self.maze_size = frame.maze.size;
+            self.maze_layer.update_maze(device, queue, frame.maze);
+            self.unreachable_layer.update_maze(device, queue, frame.unreachable);
+            self.cursor_layer.update_instances(device, queue, frame.cursor);
+        }
+        self.camera.update(queue, frame_size, self.maze_size, 0.0);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("EditorScene Render Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Draw maze and cursor"),
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clean_color),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_bind_group(0, &self.camera.uniform_bind_group, &[]);
+            self.maze_layer.sub_render_pass(&mut render_pass);
+            self.unreachable_layer.sub_render_pass(&mut render_pass);
+            self.cursor_layer.sub_render_pass(&mut render_pass);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+}
+
+impl SceneUpdater for EditorSceneUpdater {
+    fn update(
+        &self,
+        _device: &wgpu::Device,
+        _format: wgpu::TextureFormat,
+        input_center: &InputCenter,
+    ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
+        self.manage(input_center)
+            .unwrap_or_else(|err| error!("{}", err));
+        None
+    }
+}
+
+impl Drop for EditorSceneRender {
+    fn drop(&mut self) {
+        // The updater has stopped by itself if it failed to send a frame.
+        let _ = self.stop_signal_sender.send(());
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::{Deref, DerefMut};
+
+use cgmath::num_traits::FloatConst;
+use wgpu::util::DeviceExt;
+
+use super::super::render_layer::{BasicLayer, VertexAndInstances};
+use super::{Vertex, BULLET_RADIUS};
+
+/// Number of segments used to approximate a bullet circle.
+const BULLET_SEGMENTS: usize = 12;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BulletInstance {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+}
+
+pub struct BulletLayer(BasicLayer<VertexAndInstances>);
+
+impl Deref for BulletLayer {
+    type Target = BasicLayer<VertexAndInstances>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for BulletLayer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl BulletLayer {
+    pub fn new(
+        device: &wgpu::Device,
+        fragment_format: wgpu::ColorTargetState,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let pipeline = Self::pipeline(device, fragment_format, uniform_bind_group_layout);
+
+        let vertices = bullet_vertices();
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bullet Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("BulletInstance Buffer"),
+            contents: &[],
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        });
+
+        BulletLayer(BasicLayer {
+            pipeline,
+            buffer: VertexAndInstances {
+                vertex: vertex_buffer,
+                vertex_num: vertices.len(),
+                instance: instance_buffer,
+                instance_num: 0,
+                instance_capacity: 0,
+            },
+        })
+    }
+
+    fn pipeline(
+        device: &wgpu::Device,
+        fragment_format: wgpu::ColorTargetState,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let vs_module =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/bullet.vert.spv"));
+        let fs_module =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/bullet.frag.spv"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Bullet Render Pipeline Layout"),
+                bind_group_layouts: &[uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Bullet Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::InputStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float2],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<BulletInstance>() as wgpu::BufferAddress,
+                        step_mode: wgpu::InputStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![1 => Float2, 2 => Float2],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[fragment_format],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    pub fn update_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: Vec<BulletInstance>,
+    ) {
+        if self.buffer.instance_capacity < instances.len() {
+            // Recreate buffer
+            self.buffer.instance = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("BulletInstance Buffer"),
+                contents: bytemuck::cast_slice(&instances),
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            });
+            self.buffer.instance_capacity = instances.len();
+        } else if !instances.is_empty() {
+            // Just send to the existing buffer
+            queue.write_buffer(&self.buffer.instance, 0, bytemuck::cast_slice(&instances));
+        }
+        self.buffer.instance_num = instances.len();
+    }
+}
+
+/// Generate a triangle list of a circle centered at the origin.
+fn bullet_vertices() -> Vec<Vertex> {
+    let point = |i: usize| {
+        let (sin, cos) = (2.0 * f32::PI() * i as f32 / BULLET_SEGMENTS as f32).sin_cos();
+        Vertex::new(cos * BULLET_RADIUS, sin * BULLET_RADIUS)
+    };
+    (0..BULLET_SEGMENTS)
+        .flat_map(|i| vec![Vertex::new(0.0, 0.0), point(i), point(i + 1)])
+        .collect()
+}
@@ -0,0 +1,121 @@
+use cgmath::SquareMatrix;
+use rapier2d::na::{Matrix4, Vector3};
+use wgpu::util::DeviceExt;
+
+/// Size of the original movie in pixels, the window shows it as large as it fits.
+pub(crate) const MOVIE_WIDTH: f32 = 692.0;
+pub(crate) const MOVIE_HEIGHT: f32 = 480.0;
+/// The score bar below the maze.
+pub(crate) const HEIGHT_TO_BOTTOM: f32 = 80.0;
+const MOVIE_PADDING: f32 = 10.0;
+
+/// The uniform buffer shared by all layers drawing in maze coordinates.
+pub struct Camera {
+    uniforms: Uniforms,
+    uniform_buffer: wgpu::Buffer,
+    pub uniform_bind_group_layout: wgpu::BindGroupLayout,
+    pub uniform_bind_group: wgpu::BindGroup,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Uniforms {
+    view_proj: [[f32; 4]; 4],
+    forecast: f32,
+}
+
+impl Camera {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let uniforms = Uniforms {
+            view_proj: cgmath::Matrix4::identity().into(),
+            forecast: 0.0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tank Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        });
+
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("uniform_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("uniform_bind_group"),
+        });
+
+        Camera {
+            uniforms,
+            uniform_buffer,
+            uniform_bind_group_layout,
+            uniform_bind_group,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        frame_size: [u32; 2],
+        maze_size: [usize; 2],
+        forecast: f32,
+    ) {
+        let frame_size = [frame_size[0] as f32, frame_size[1] as f32];
+        self.write(queue, projection(&frame_size, &maze_size), forecast);
+    }
+
+    /// Draw in pixels of the movie instead of the maze, the origin is at its center.
+    pub fn update_hud(&mut self, queue: &wgpu::Queue, frame_size: [u32; 2]) {
+        let frame_size = [frame_size[0] as f32, frame_size[1] as f32];
+        self.write(queue, hud_projection(&frame_size), 0.0);
+    }
+
+    fn write(&mut self, queue: &wgpu::Queue, view_proj: Matrix4<f32>, forecast: f32) {
+        self.uniforms = Uniforms {
+            view_proj: view_proj.into(),
+            forecast,
+        };
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniforms]),
+        );
+    }
+}
+
+#[inline]
+pub fn projection(frame_size: &[f32; 2], maze_size: &[usize; 2]) -> Matrix4<f32> {
+    const VIEW_WIDTH: f32 = MOVIE_WIDTH - MOVIE_PADDING;
+    const VIEW_HEIGHT: f32 = MOVIE_HEIGHT - MOVIE_PADDING - HEIGHT_TO_BOTTOM;
+
+    let maze_size = [maze_size[0] as f32 + 0.125, maze_size[1] as f32 + 0.125];
+    let basic_scale = (VIEW_WIDTH / maze_size[0]).min(VIEW_HEIGHT / maze_size[1]);
+    let maze_to_movie = Matrix4::identity()
+        .append_scaling(basic_scale)
+        .append_translation(&Vector3::new(0.0, HEIGHT_TO_BOTTOM / 2.0, 0.0));
+    hud_projection(frame_size) * maze_to_movie
+}
+
+#[inline]
+pub fn hud_projection(frame_size: &[f32; 2]) -> Matrix4<f32> {
+    let window_scale = (frame_size[0] / MOVIE_WIDTH).min(frame_size[1] / MOVIE_HEIGHT) * 2.0;
+    Matrix4::identity().append_nonuniform_scaling(&Vector3::new(
+        window_scale / frame_size[0],
+        window_scale / frame_size[1],
+        1.0,
+    ))
+}
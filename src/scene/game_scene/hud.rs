@@ -0,0 +1,185 @@
+//! The score bar below the maze, and a banner over it when a round is over.
+//! Everything here is in pixels of the movie, see [`Camera::update_hud`].
+//!
+//! [`Camera::update_hud`]: super::camera::Camera::update_hud
+use crate::scene::palette::{self, COLOURS};
+
+use super::{
+    camera::{HEIGHT_TO_BOTTOM, MOVIE_HEIGHT, MOVIE_WIDTH},
+    shape_layer::ShapeData,
+    simulation::Snapshot,
+    text_layer::{Align, Text},
+};
+
+const DARK: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const LIGHT: [f32; 4] = [0.75, 0.75, 0.75, 1.0];
+const BAR: [f32; 4] = [0.92, 0.92, 0.92, 1.0];
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+/// Room on the right of the bar for the round number.
+const ROUND_WIDTH: f32 = 110.0;
+
+/// What the HUD shows, it's drawn again only when this changes.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Hud {
+    round: u32,
+    players: Vec<HudPlayer>,
+    /// Some if the round is over, with the last tank left if there is one.
+    round_over: Option<Option<usize>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct HudPlayer {
+    /// Index in [`COLOURS`].
+    colour: usize,
+    score: u32,
+    alive: bool,
+}
+
+impl Hud {
+    /// `colours` of the players as in [`FrameData::new`].
+    ///
+    /// [`FrameData::new`]: super::FrameData::new
+    pub fn new(snapshot: &Snapshot, colours: &[usize]) -> Hud {
+        let players: Vec<HudPlayer> = snapshot
+            .tanks
+            .iter()
+            .zip(snapshot.scores.iter())
+            .enumerate()
+            .map(|(i, (tank, &score))| HudPlayer {
+                colour: palette::player_colour(colours, i),
+                score,
+                alive: tank.alive,
+            })
+            .collect();
+        let mut alive = players.iter().enumerate().filter(|(_, player)| player.alive);
+        let round_over = match (alive.next(), alive.next()) {
+            _ if players.len() < 2 => None,
+            (Some((winner, _)), None) => Some(Some(winner)),
+            (None, _) => Some(None),
+            _ => None,
+        };
+        Hud {
+            round: snapshot.round,
+            players,
+            round_over,
+        }
+    }
+
+    pub fn draw(&self) -> (ShapeData, Vec<Text>) {
+        let mut shapes = ShapeData::default();
+        let mut texts = Vec::new();
+        let left = -MOVIE_WIDTH / 2.0;
+        let center_y = (HEIGHT_TO_BOTTOM - MOVIE_HEIGHT) / 2.0;
+        shapes.rect([0.0, center_y], [MOVIE_WIDTH, HEIGHT_TO_BOTTOM], BAR);
+        texts.push(Text {
+            content: format!("Round {}", self.round),
+            position: [MOVIE_WIDTH / 2.0 - 16.0, center_y - 6.0],
+            size: 18.0,
+            colour: DARK,
+            align: Align::Right,
+        });
+
+        // Players share the rest of the bar.
+        let width = (MOVIE_WIDTH - ROUND_WIDTH - 10.0) / self.players.len().max(1) as f32;
+        for (i, player) in self.players.iter().enumerate() {
+            let center = left + 10.0 + width * (i as f32 + 0.5);
+            let colour = if player.alive {
+                COLOURS[player.colour].rgba()
+            } else {
+                LIGHT
+            };
+            draw_tank(&mut shapes, [center - 14.0, center_y], colour);
+            texts.push(Text {
+                content: player.score.to_string(),
+                position: [center + 2.0, center_y - 8.0],
+                size: 24.0,
+                colour: DARK,
+                align: Align::Left,
+            });
+        }
+
+        if let Some(winner) = self.round_over {
+            // Over the middle of the maze.
+            let center_y = HEIGHT_TO_BOTTOM / 2.0;
+            let stripe = match winner {
+                Some(winner) => COLOURS[self.players[winner].colour].rgba(),
+                None => LIGHT,
+            };
+            shapes.rect([0.0, center_y], [MOVIE_WIDTH, 80.0], WHITE);
+            shapes.rect([0.0, center_y + 36.0], [MOVIE_WIDTH, 8.0], stripe);
+            shapes.rect([0.0, center_y - 36.0], [MOVIE_WIDTH, 8.0], stripe);
+            texts.push(Text {
+                content: "Round over".to_string(),
+                position: [0.0, center_y + 2.0],
+                size: 30.0,
+                colour: DARK,
+                align: Align::Center,
+            });
+            let result = match winner {
+                Some(winner) => {
+                    let name = COLOURS[self.players[winner].colour].name;
+                    format!("{}{} wins", name[..1].to_uppercase(), &name[1..])
+                }
+                None => "Draw".to_string(),
+            };
+            texts.push(Text {
+                content: result,
+                position: [0.0, center_y - 24.0],
+                size: 20.0,
+                colour: DARK,
+                align: Align::Center,
+            });
+        }
+        (shapes, texts)
+    }
+}
+
+/// A tank facing right.
+fn draw_tank(shapes: &mut ShapeData, center: [f32; 2], colour: [f32; 4]) {
+    let [x, y] = center;
+    shapes.rect([x, y + 9.0], [26.0, 5.0], DARK);
+    shapes.rect([x, y - 9.0], [26.0, 5.0], DARK);
+    shapes.rect([x, y], [22.0, 18.0], colour);
+    shapes.circle([x, y], 5.0, DARK);
+    shapes.rect([x + 10.0, y], [16.0, 3.0], DARK);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::simulation::TankState;
+    use super::*;
+
+    fn snapshot(alive: &[bool]) -> Snapshot {
+        Snapshot {
+            seq_number: 0,
+            round: 2,
+            scores: vec![1; alive.len()],
+            tanks: alive
+                .iter()
+                .map(|&alive| TankState {
+                    alive,
+                    ..TankState::default()
+                })
+                .collect(),
+            bullets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn round_is_over_with_one_tank_left() {
+        assert_eq!(Hud::new(&snapshot(&[true, true, false]), &[]).round_over, None);
+        assert_eq!(Hud::new(&snapshot(&[false, true, false]), &[]).round_over, Some(Some(1)));
+        assert_eq!(Hud::new(&snapshot(&[false, false]), &[]).round_over, Some(None));
+    }
+
+    #[test]
+    fn players_have_picked_colours() {
+        let colours = |picked: &[usize]| -> Vec<usize> {
+            let hud = Hud::new(&snapshot(&[true, true, true]), picked);
+            hud.players.iter().map(|player| player.colour).collect()
+        };
+        assert_eq!(colours(&[5, 2, 7]), vec![5, 2, 7]);
+        // Replays don't know the colours.
+        assert_eq!(colours(&[]), vec![0, 1, 2]);
+    }
+}
@@ -2,7 +2,10 @@ use std::ops::{Deref, DerefMut};
 
 use wgpu::util::DeviceExt;
 
-use crate::scene::render_layer::{BasicLayer, VertexAndIndexes};
+use crate::scene::{
+    maze::Maze,
+    render_layer::{BasicLayer, VertexAndIndexes},
+};
 
 use super::Vertex;
 
@@ -14,6 +17,17 @@ pub struct MazeData {
 
 pub struct MazeLayer(BasicLayer<VertexAndIndexes>);
 
+impl From<&Maze> for MazeData {
+    fn from(maze: &Maze) -> Self {
+        let (vertex, index) = maze.triangle_mesh();
+        MazeData {
+            vertex,
+            index,
+            size: [maze.width, maze.height],
+        }
+    }
+}
+
 impl Deref for MazeLayer {
     type Target = BasicLayer<VertexAndIndexes>;
 
@@ -1,75 +1,118 @@
-use std::{error::Error, time};
+use std::{error::Error, path::PathBuf, str::FromStr, time};
 use std::cell::RefCell;
 
-use cgmath::SquareMatrix;
 use crossbeam_channel::{bounded, Receiver, Select, Sender, tick};
 #[allow(unused_imports)]
 use log::{debug, error, info, log_enabled};
-use rapier2d::{
-    dynamics::{IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle, RigidBodySet},
-    geometry::{BroadPhase, ColliderBuilder, ColliderHandle, ColliderSet, NarrowPhase},
-    math::{Point, Rotation},
-    na::{Matrix4, Rotation2, Vector2, Vector3},
-    pipeline::PhysicsPipeline,
-};
-use wgpu::util::DeviceExt;
+use rapier2d::geometry::InteractionGroups;
+use serde::{Deserialize, Serialize};
 
+use bullet_layer::{BulletInstance, BulletLayer};
+use camera::Camera;
+use hud::Hud;
 use maze_layer::{MazeData, MazeLayer};
+use replay::Replay;
+use shape_layer::ShapeLayer;
+use simulation::{Simulation, Snapshot};
 use tank_layer::{TankInstance, TankLayer};
+use text_layer::TextLayer;
 
 use crate::input::{Controller, input_center::InputCenter};
 
-use super::{maze::Maze, render_layer::Layer, SceneRender, SceneUpdater};
+use super::{maze::Maze, palette, render_layer::Layer, SceneRender, SceneUpdater};
 
-mod maze_layer;
+pub(crate) mod bullet_layer;
+pub(crate) mod camera;
+mod hud;
+pub(crate) mod maze_layer;
+pub(crate) mod replay;
+pub(crate) mod shape_layer;
+pub(crate) mod text_layer;
+pub(crate) mod simulation;
 mod tank_layer;
 
-const PHYSICAL_DT: f32 = 1.0 / 90.0;
+pub(crate) const PHYSICAL_DT: f32 = 1.0 / 90.0;
+
+const BULLET_RADIUS: f32 = 0.05;
+const BULLET_SPEED: f32 = 5.0;
+/// How many physic ticks a bullet lives before it disappears.
+const BULLET_LIFETIME: u32 = (8.0 / PHYSICAL_DT) as u32;
+/// How many bullets of one tank can exist at the same time.
+const MAX_BULLETS_PER_TANK: usize = 5;
+/// How many physic ticks to wait before starting the next round,
+/// the last tank can still be destroyed during this time.
+const ROUND_END_DELAY: u32 = (3.0 / PHYSICAL_DT) as u32;
+
+const MAZE_GROUP: InteractionGroups = InteractionGroups::new(0b001, 0b111);
+const TANK_GROUP: InteractionGroups = InteractionGroups::new(0b010, 0b111);
+/// Bullets collide with the maze and tanks, but pass through each other.
+const BULLET_GROUP: InteractionGroups = InteractionGroups::new(0b100, 0b011);
+
+/// Shapes of the maze in the physic world.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WallColliders {
+    /// A cuboid for each merged wall, fast bullets bounce off the flat sides reliably.
+    Cuboids,
+    /// A triangle mesh of the same shape as the rendered walls.
+    Trimesh,
+}
 
 pub struct GameSceneRender {
     clean_color: wgpu::Color,
 
-    uniforms: Uniforms,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
+    camera: Camera,
 
     tank_layer: TankLayer,
+    bullet_layer: BulletLayer,
     maze_layer: MazeLayer,
 
+    hud_camera: Camera,
+    hud_shape_layer: ShapeLayer,
+    hud_text_layer: TextLayer,
+    /// What the HUD layers show.
+    hud: Option<Hud>,
+
     maze_size: [usize; 2],
 
-    tank_update_chan: Receiver<Vec<TankInstance>>,
+    frame_update_chan: Receiver<FrameData>,
     maze_update_chan: Receiver<MazeData>,
     stop_signal_sender: Sender<()>,
+    stopped_chan: Receiver<()>,
 
     last_update: time::Instant,
 }
 
 pub struct GameSceneUpdater {
-    physical: RefCell<PhysicalStatus>,
-
-    tank_update_sender: Sender<Vec<TankInstance>>,
-    maze_update_sender: Sender<MazeData>,
-    stop_signal_chan: Receiver<()>,
+    controllers: RefCell<Vec<Box<dyn Controller>>>,
+    /// Index in [`palette::COLOURS`] of each player.
+    colours: RefCell<Vec<usize>>,
+    /// All random things in the match are generated from this seed.
+    seed: u64,
+    /// The fixed maze of every round, random ones are generated if it's None.
+    arena: Option<Maze>,
+    colliders: WallColliders,
+    /// Save the replay of the match to this file when the scene stops.
+    record: RefCell<Option<PathBuf>>,
+
+    channels: RenderChannels,
 }
 
-struct PhysicalStatus {
-    tanks: Vec<PhysicTank>,
-    seq_number: u32,
-
-    pipeline: PhysicsPipeline,
-    integration_parameters: IntegrationParameters,
-    broad_phase: BroadPhase,
-    narrow_phase: NarrowPhase,
-    rigid_body_set: RigidBodySet,
-    collider_set: ColliderSet,
-    joint_set: JointSet,
+/// The update thread's end of the channels to a [`GameSceneRender`],
+/// any scene simulating a match can draw it through them.
+pub(crate) struct RenderChannels {
+    pub frame_update_sender: Sender<FrameData>,
+    pub maze_update_sender: Sender<MazeData>,
+    pub stop_signal_chan: Receiver<()>,
+    /// Nothing is sent through it, the render knows the update thread has finished
+    /// with the match, e.g. saved the replay, when it's dropped along with the updater.
+    pub _stopped_sender: Sender<()>,
 }
 
-struct PhysicTank {
-    controller: Box<dyn Controller>,
-    rigid_body_handle: RigidBodyHandle,
-    collider_handle: ColliderHandle,
+/// All instances needed to draw one frame.
+pub(crate) struct FrameData {
+    tanks: Vec<TankInstance>,
+    bullets: Vec<BulletInstance>,
+    hud: Hud,
 }
 
 #[repr(C)]
@@ -84,18 +127,34 @@ impl Vertex {
     }
 }
 
-#[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct Uniforms {
-    view_proj: [[f32; 4]; 4],
-    forecast: f32,
-}
-
 pub(crate) fn new(
     device: &wgpu::Device,
     format: wgpu::TextureFormat,
+    seed: u64,
+    arena: Option<Maze>,
+    colliders: WallColliders,
 ) -> (GameSceneRender, GameSceneUpdater) {
-    info!("Creating GameScene");
+    info!("Creating GameScene with seed {}", seed);
+    let (render, channels) = render(device, format);
+    (
+        render,
+        GameSceneUpdater {
+            controllers: RefCell::new(Vec::new()),
+            colours: RefCell::new(Vec::new()),
+            seed,
+            arena,
+            colliders,
+            record: RefCell::new(None),
+            channels,
+        },
+    )
+}
+
+/// Create the render of a match, which draws the frames and mazes sent to the channels.
+pub(crate) fn render(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> (GameSceneRender, RenderChannels) {
     let clean_color = wgpu::Color {
         r: 1.0,
         g: 1.0,
@@ -103,178 +162,184 @@ pub(crate) fn new(
         a: 1.0,
     };
 
-    let uniforms = Uniforms {
-        view_proj: cgmath::Matrix4::identity().into(),
-        forecast: 0.0,
-    };
-    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Tank Uniform Buffer"),
-        contents: bytemuck::cast_slice(&[uniforms]),
-        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-    });
-
-    let uniform_bind_group_layout =
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("uniform_bind_group_layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-    let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        layout: &uniform_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: uniform_buffer.as_entire_binding(),
-        }],
-        label: Some("uniform_bind_group"),
-    });
-
-    let tank_layer = TankLayer::new(device, format.into(), &uniform_bind_group_layout);
-    let maze_layer = MazeLayer::new(device, format.into(), &uniform_bind_group_layout);
+    let camera = Camera::new(device);
+    let uniform_bind_group_layout = &camera.uniform_bind_group_layout;
+    let tank_layer = TankLayer::new(device, format.into(), uniform_bind_group_layout);
+    let bullet_layer = BulletLayer::new(device, format.into(), uniform_bind_group_layout);
+    let maze_layer = MazeLayer::new(device, format.into(), uniform_bind_group_layout);
+
+    let hud_camera = Camera::new(device);
+    let hud_bind_group_layout = &hud_camera.uniform_bind_group_layout;
+    let hud_shape_layer = ShapeLayer::new(device, format.into(), hud_bind_group_layout);
+    let hud_text_layer = TextLayer::new(device, format, hud_bind_group_layout);
 
     // Start physic emulation
-    let (tank_update_sender, tank_update_chan) = bounded(0);
-    let (maze_update_sender, maze_update_chan) = bounded(0);
+    let (frame_update_sender, frame_update_chan) = bounded(0);
+    // A new maze is sent at the beginning of every round, so don't block the
+    // physic thread if the render haven't taken the previous one.
+    let (maze_update_sender, maze_update_chan) = bounded(1);
     let (stop_signal_sender, stop_signal_chan) = bounded(0);
-
-    let physical = RefCell::new(PhysicalStatus {
-        tanks: Vec::new(),
-        seq_number: 0,
-        pipeline: PhysicsPipeline::new(),
-        integration_parameters: IntegrationParameters::default(),
-        broad_phase: BroadPhase::new(),
-        narrow_phase: NarrowPhase::new(),
-        rigid_body_set: RigidBodySet::new(),
-        collider_set: ColliderSet::new(),
-        joint_set: JointSet::new(),
-    });
+    let (stopped_sender, stopped_chan) = bounded(0);
 
     (
         GameSceneRender {
             clean_color,
-            uniforms,
-            uniform_buffer,
-            uniform_bind_group,
+            camera,
             tank_layer,
+            bullet_layer,
             maze_layer,
+            hud_camera,
+            hud_shape_layer,
+            hud_text_layer,
+            hud: None,
             maze_size: [1, 1],
 
-            tank_update_chan,
+            frame_update_chan,
             maze_update_chan,
             stop_signal_sender,
+            stopped_chan,
 
             last_update: time::Instant::now(),
         },
-        GameSceneUpdater {
-            physical,
-            tank_update_sender,
+        RenderChannels {
+            frame_update_sender,
             maze_update_sender,
             stop_signal_chan,
+            _stopped_sender: stopped_sender,
         },
     )
 }
 
 impl GameSceneUpdater {
     fn manage(&self, input_center: &InputCenter) -> Result<(), Box<dyn Error>> {
-        let mut physical = self.physical.borrow_mut();
-        physical.integration_parameters.dt = PHYSICAL_DT;
+        let controllers = self.controllers.borrow();
+        let colours = self.colours.borrow();
+        let mut simulation = Simulation::new(
+            controllers.len(),
+            self.seed,
+            self.arena.clone(),
+            self.colliders,
+        );
+        let mut replay = self.record.borrow().as_ref().map(|_| {
+            Replay::new(
+                controllers.len(),
+                self.seed,
+                self.arena.clone(),
+                self.colliders,
+            )
+        });
         let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        // The round whose maze has been sent to the render.
+        let mut round = 0;
+
+        loop {
+            input_center.update();
+            let inputs = simulation.poll_inputs(&controllers);
+            let snapshot = simulation.step(&inputs);
+            if let Some(replay) = &mut replay {
+                replay.record(&inputs);
+            }
+            if snapshot.round != round {
+                round = snapshot.round;
+                let maze = MazeData::from(simulation.maze());
+                self.channels.maze_update_sender.send(maze)?;
+            }
+
+            if !self.channels.wait_tick(&ticker, Some(FrameData::new(&snapshot, &colours)))? {
+                break;
+            }
+        }
+        if let (Some(replay), Some(path)) = (replay, &*self.record.borrow()) {
+            replay.save(path)?;
+            info!("Replay of {} ticks is saved to {}", replay.ticks(), path.display());
+        }
+        Ok(())
+    }
 
-        let maze = Maze::new(&mut rand::thread_rng());
-
-        // Generate mesh for render
-        let (maze_mesh_vertices, maze_mesh_indexes) = maze.triangle_mesh();
-        self.maze_update_sender.send(MazeData {
-            vertex: maze_mesh_vertices,
-            index: maze_mesh_indexes,
-            size: [maze.width, maze.height],
-        })?;
-
-        // Generate mesh for physic
-        let (maze_mesh_vertices, maze_mesh_indexes) = maze.triangle_mesh();
-        physical.add_maze(maze_mesh_vertices, maze_mesh_indexes);
-
-        'next_update: loop {
-            input_center.update(|_| (), |_, _| ())?;
-            physical.update_tick();
-            let mut update_data = Some(
-                physical
-                    .tanks
-                    .iter()
-                    .map(|tank| {
-                        let rigid_body =
-                            physical.rigid_body_set.get(tank.rigid_body_handle).unwrap();
-                        let position = rigid_body.position();
-                        let velocity = rigid_body.linvel();
-                        TankInstance {
-                            position: position.translation.vector.into(),
-                            velocity: [velocity.x, velocity.y],
-                            rotation: position.rotation.angle(),
-                            rotation_v: rigid_body.angvel(),
-                        }
-                    })
-                    .collect::<Vec<TankInstance>>(),
-            );
-
-            // Wait for next tick, and do other things on idle time.
-            // I didn't use 'select!' marco here because we need
-            // delete update_sender after send once.
-            let mut selector = Select::new();
-            let i_ticker = selector.recv(&ticker);
-            let i_update_sender = selector.send(&self.tank_update_sender);
-            let i_stop_receiver = selector.recv(&self.stop_signal_chan);
-
-            loop {
-                let oper = selector.select();
-                match oper.index() {
-                    i if i == i_stop_receiver => {
-                        oper.recv(&self.stop_signal_chan)?;
-                        return Ok(());
-                    }
-                    i if i == i_ticker => {
-                        oper.recv(&ticker)?;
-                        continue 'next_update;
-                    }
-                    i if i == i_update_sender => {
-                        // This unwrap() never panic because this channel
-                        // is delete from selector next line.
-                        oper.send(&self.tank_update_sender, update_data.take().unwrap())?;
-                        selector.remove(i_update_sender);
-                    }
-                    _ => unreachable!(),
+    /// The tank is placed into the maze when the match begins, `colour` is an index in
+    /// [`palette::COLOURS`].
+    pub fn add_player(&self, controller: Box<dyn Controller>, colour: usize) {
+        self.controllers.borrow_mut().push(controller);
+        self.colours.borrow_mut().push(colour);
+    }
+
+    /// Record the inputs of the match, and save them to the file when the scene stops.
+    pub fn record_to(&self, path: PathBuf) {
+        *self.record.borrow_mut() = Some(path);
+    }
+}
+
+impl RenderChannels {
+    /// Offer the frame to the render until the next tick, returns false if the render is dropped.
+    pub fn wait_tick(
+        &self,
+        ticker: &Receiver<time::Instant>,
+        mut frame: Option<FrameData>,
+    ) -> Result<bool, Box<dyn Error>> {
+        // Wait for next tick, and do other things on idle time.
+        // I didn't use 'select!' marco here because we need
+        // delete update_sender after send once.
+        let mut selector = Select::new();
+        let i_ticker = selector.recv(ticker);
+        let i_stop_receiver = selector.recv(&self.stop_signal_chan);
+        let mut i_update_sender = frame
+            .as_ref()
+            .map(|_| selector.send(&self.frame_update_sender));
+
+        loop {
+            let oper = selector.select();
+            match oper.index() {
+                i if i == i_stop_receiver => {
+                    oper.recv(&self.stop_signal_chan)?;
+                    return Ok(false);
+                }
+                i if i == i_ticker => {
+                    oper.recv(ticker)?;
+                    return Ok(true);
+                }
+                i if Some(i) == i_update_sender => {
+                    // This unwrap() never panic because this channel
+                    // is delete from selector next line.
+                    oper.send(&self.frame_update_sender, frame.take().unwrap())?;
+                    selector.remove(i);
+                    i_update_sender = None;
                 }
+                _ => unreachable!(),
             }
         }
     }
+}
 
-    pub fn add_player(&self, controller: Box<dyn Controller>) {
-        let physical = &mut *self.physical.borrow_mut();
-        let right_body = RigidBodyBuilder::new_dynamic()
-            .can_sleep(true)
-            .mass(0.9)
-            .linear_damping(10.0)
-            .principal_angular_inertia(0.8)
-            .angular_damping(10.0)
-            .build();
-        let collider = ColliderBuilder::cuboid(0.2, 0.25).build();
-        let rigid_body_handle = physical.rigid_body_set.insert(right_body);
-        let collider_handle =
-            physical
-                .collider_set
-                .insert(collider, rigid_body_handle, &mut physical.rigid_body_set);
-
-        physical.tanks.push(PhysicTank {
-            controller,
-            rigid_body_handle,
-            collider_handle,
-        });
+impl FrameData {
+    /// `colours` are indexes in [`palette::COLOURS`] of the players,
+    /// see [`palette::player_colour`] for players without one.
+    pub fn new(snapshot: &Snapshot, colours: &[usize]) -> Self {
+        let tanks = snapshot
+            .tanks
+            .iter()
+            .enumerate()
+            .filter(|(_, tank)| tank.alive)
+            .map(|(i, tank)| TankInstance {
+                position: tank.position,
+                velocity: tank.velocity,
+                rotation: tank.rotation,
+                rotation_v: tank.angular_velocity,
+                colour: palette::COLOURS[palette::player_colour(colours, i)].rgb,
+            })
+            .collect();
+        let bullets = snapshot
+            .bullets
+            .iter()
+            .map(|bullet| BulletInstance {
+                position: bullet.position,
+                velocity: bullet.velocity,
+            })
+            .collect();
+        FrameData {
+            tanks,
+            bullets,
+            hud: Hud::new(snapshot, colours),
+        }
     }
 }
 
@@ -287,25 +352,26 @@ impl SceneRender for GameSceneRender {
         frame_size: [u32; 2],
     ) -> Result<(), wgpu::SwapChainError> {
         // Update data from physical thread
-        if let Ok(instances) = self.tank_update_chan.try_recv() {
+        if let Ok(frame) = self.frame_update_chan.try_recv() {
             self.last_update = time::Instant::now();
-            self.tank_layer.update_instances(device, queue, instances);
+            self.tank_layer.update_instances(device, queue, frame.tanks);
+            self.bullet_layer.update_instances(device, queue, frame.bullets);
+            if self.hud.as_ref() != Some(&frame.hud) {
+                let (shapes, texts) = frame.hud.draw();
+                self.hud_shape_layer.update_shapes(device, shapes);
+                self.hud_text_layer.update_texts(device, queue, &texts);
+                self.hud = Some(frame.hud);
+            }
         }
         if let Ok(maze_data) = self.maze_update_chan.try_recv() {
             self.maze_size = maze_data.size;
             self.maze_layer.update_maze(device, queue, maze_data);
         }
         // Update uniform
-        let frame_size = [frame_size[0] as f32, frame_size[1] as f32];
-        self.uniforms = Uniforms {
-            view_proj: projection(&frame_size, &self.maze_size).into(),
-            forecast: PHYSICAL_DT.min(self.last_update.elapsed().as_secs_f32() * 0.99), // do not forecast greater then physic engine
-        };
-        queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[self.uniforms]),
-        );
+        // do not forecast greater then physic engine
+        let forecast = PHYSICAL_DT.min(self.last_update.elapsed().as_secs_f32() * 0.99);
+        self.camera.update(queue, frame_size, self.maze_size, forecast);
+        self.hud_camera.update_hud(queue, frame_size);
         // Building command buffer
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("GameScene Render Encoder"),
@@ -325,7 +391,7 @@ impl SceneRender for GameSceneRender {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+            render_pass.set_bind_group(0, &self.camera.uniform_bind_group, &[]);
 
             render_pass.push_debug_group("Draw maze");
             self.maze_layer.sub_render_pass(&mut render_pass);
@@ -334,6 +400,16 @@ impl SceneRender for GameSceneRender {
             render_pass.push_debug_group("Draw tanks");
             self.tank_layer.sub_render_pass(&mut render_pass);
             render_pass.pop_debug_group();
+
+            render_pass.push_debug_group("Draw bullets");
+            self.bullet_layer.sub_render_pass(&mut render_pass);
+            render_pass.pop_debug_group();
+
+            render_pass.push_debug_group("Draw HUD");
+            render_pass.set_bind_group(0, &self.hud_camera.uniform_bind_group, &[]);
+            self.hud_shape_layer.sub_render_pass(&mut render_pass);
+            self.hud_text_layer.sub_render_pass(&mut render_pass);
+            render_pass.pop_debug_group();
         }
         encoder.pop_debug_group();
 
@@ -359,72 +435,87 @@ impl SceneUpdater for GameSceneUpdater {
 
 impl Drop for GameSceneRender {
     fn drop(&mut self) {
-        // This will block until update thread quit
-        self.stop_signal_sender.send(()).unwrap();
+        stop_updater(&self.stop_signal_sender, &self.stopped_chan);
     }
 }
 
-impl PhysicalStatus {
-    fn update_tick(&mut self) {
-        let gravity = Vector2::new(0.0, 0.0);
-
-        // Apply the control to the tank.
-        for tank in self.tanks.iter() {
-            let (rot, acl) = tank.controller.movement_status();
-            let right_body = &mut self.rigid_body_set[tank.rigid_body_handle];
-            let rotation = &Rotation2::from(right_body.position().rotation);
-            right_body.apply_force(rotation * Vector2::new(0.0, acl * 30.0), true);
-            right_body.apply_torque(-rot * 40.0, true);
-            right_body.set_linvel(
-                Rotation::new(right_body.angvel() * PHYSICAL_DT) * right_body.linvel(),
-                true,
-            );
-        }
+/// Ask the update thread to stop the match, and block until it has finished with it,
+/// otherwise the process may exit before the replay is saved.
+fn stop_updater(stop_signal_sender: &Sender<()>, stopped_chan: &Receiver<()>) {
+    // The updater may have stopped by itself, e.g. when the server stops answering.
+    let _ = stop_signal_sender.send(());
+    // Returns an error once the updater drops the sender.
+    let _ = stopped_chan.recv();
+}
 
-        self.pipeline.step(
-            &gravity,
-            &self.integration_parameters,
-            &mut self.broad_phase,
-            &mut self.narrow_phase,
-            &mut self.rigid_body_set,
-            &mut self.collider_set,
-            &mut self.joint_set,
-            None,
-            None,
-            &(),
-        );
-        // Increase simulate sequence number.
-        self.seq_number += 1;
+impl Default for WallColliders {
+    fn default() -> Self {
+        WallColliders::Cuboids
     }
+}
 
-    pub fn add_maze(&mut self, vertices: Vec<Point<f32>>, indices: Vec<[u32; 3]>) {
-        let right_body = RigidBodyBuilder::new_static().build();
-        let collider = ColliderBuilder::trimesh(vertices, indices).build();
-        let rigid_body_handle = self.rigid_body_set.insert(right_body);
-        let _collider_handle =
-            self.collider_set
-                .insert(collider, rigid_body_handle, &mut self.rigid_body_set);
+impl FromStr for WallColliders {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cuboids" => Ok(WallColliders::Cuboids),
+            "trimesh" => Ok(WallColliders::Trimesh),
+            _ => Err(format!("unknown wall colliders: {}", s)),
+        }
     }
 }
 
-#[inline]
-fn projection(frame_size: &[f32; 2], maze_size: &[usize; 2]) -> Matrix4<f32> {
-    const MOVIE_WIDTH: f32 = 692.0;
-    const MOVIE_HEIGHT: f32 = 480.0;
-    const HEIGHT_TO_BOTTOM: f32 = 80.0;
-    const MOVIE_PADDING: f32 = 10.0;
-    const VIEW_WIDTH: f32 = MOVIE_WIDTH - MOVIE_PADDING;
-    const VIEW_HEIGHT: f32 = MOVIE_HEIGHT - MOVIE_PADDING - HEIGHT_TO_BOTTOM;
-
-    let maze_size = [maze_size[0] as f32 + 0.125, maze_size[1] as f32 + 0.125];
-    let basic_scale = (VIEW_WIDTH / maze_size[0]).min(VIEW_HEIGHT / maze_size[1]);
-    let window_scale = (frame_size[0] / MOVIE_WIDTH).min(frame_size[1] / MOVIE_HEIGHT) * 2.0;
-    Matrix4::identity()
-        .append_scaling(basic_scale)
-        .append_translation(&Vector3::new(0.0, HEIGHT_TO_BOTTOM / 2.0, 0.0))
-        .append_nonuniform_scaling(&Vector3::new(
-            window_scale / frame_size[0],
-            window_scale / frame_size[1],
-            1.0,
-        ))
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread};
+
+    use crate::input::PlayerInput;
+
+    use super::*;
+
+    #[test]
+    fn stopping_waits_for_the_replay_to_be_saved() {
+        let (frame_update_sender, _frame_update_chan) = bounded(0);
+        let (maze_update_sender, _maze_update_chan) = bounded(1);
+        let (stop_signal_sender, stop_signal_chan) = bounded(0);
+        let (stopped_sender, stopped_chan) = bounded(0);
+        let channels = RenderChannels {
+            frame_update_sender,
+            maze_update_sender,
+            stop_signal_chan,
+            _stopped_sender: stopped_sender,
+        };
+        let path = std::env::temp_dir().join("open_tanktrouble_record_test.ottr");
+        let _ = fs::remove_file(&path);
+
+        let updater = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let ticker = tick(time::Duration::from_millis(1));
+                let mut replay = Replay::new(1, 3, None, WallColliders::Cuboids);
+                while channels.wait_tick(&ticker, None).unwrap() {
+                    replay.record(&[PlayerInput::default()]);
+                }
+                // Saving takes a while, the render must not return before it's done.
+                thread::sleep(time::Duration::from_millis(100));
+                replay.save(&path).unwrap();
+            })
+        };
+        thread::sleep(time::Duration::from_millis(20));
+        stop_updater(&stop_signal_sender, &stopped_chan);
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(replay.ticks() > 0);
+        updater.join().unwrap();
+    }
+
+    #[test]
+    fn stopping_a_finished_updater_does_not_block() {
+        let (stop_signal_sender, stop_signal_chan) = bounded(0);
+        let (stopped_sender, stopped_chan) = bounded::<()>(0);
+        drop((stop_signal_chan, stopped_sender));
+        stop_updater(&stop_signal_sender, &stopped_chan);
+    }
 }
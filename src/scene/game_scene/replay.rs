@@ -0,0 +1,265 @@
+//! Record the inputs of a match, and play it again exactly.
+//!
+//! A replay file is `OTTR`, a little-endian `u16` version, then the [`Replay`] in bincode.
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Controller, PlayerInput};
+use crate::scene::maze::Maze;
+
+use super::{simulation::Simulation, WallColliders};
+
+const MAGIC: &[u8; 4] = b"OTTR";
+const VERSION: u16 = 1;
+
+/// The settings of a match and the inputs of every tick, which decide everything happened.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub players: usize,
+    pub seed: u64,
+    pub arena: Option<Maze>,
+    pub colliders: WallColliders,
+    ticks: u32,
+    /// Inputs of each player, as `(ticks, input)` runs, most inputs last for many ticks.
+    inputs: Vec<Vec<(u32, PlayerInput)>>,
+}
+
+/// A controller repeating the recorded inputs of a player, one tick each time
+/// [`Controller::input`] is called.
+pub struct ReplayController {
+    runs: Vec<(u32, PlayerInput)>,
+    cursor: Mutex<Cursor>,
+}
+
+#[derive(Default)]
+struct Cursor {
+    run: usize,
+    /// How many ticks of the run are used.
+    used: u32,
+    current: PlayerInput,
+}
+
+impl Replay {
+    pub fn new(players: usize, seed: u64, arena: Option<Maze>, colliders: WallColliders) -> Replay {
+        Replay {
+            players,
+            seed,
+            arena,
+            colliders,
+            ticks: 0,
+            inputs: vec![Vec::new(); players],
+        }
+    }
+
+    /// Append the inputs of a tick, in the order of players.
+    pub fn record(&mut self, inputs: &[PlayerInput]) {
+        for (runs, &input) in self.inputs.iter_mut().zip(inputs) {
+            match runs.last_mut() {
+                Some((ticks, last)) if *last == input => *ticks += 1,
+                _ => runs.push((1, input)),
+            }
+        }
+        self.ticks += 1;
+    }
+
+    /// Number of recorded ticks.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// A simulation at the beginning of the recorded match.
+    pub fn simulation(&self) -> Simulation {
+        Simulation::new(self.players, self.seed, self.arena.clone(), self.colliders)
+    }
+
+    /// Controllers of all players, playing from the first tick.
+    pub fn controllers(&self) -> Vec<Box<dyn Controller>> {
+        self.controllers_at(0)
+    }
+
+    /// Controllers of all players, playing from the tick.
+    pub fn controllers_at(&self, tick: u32) -> Vec<Box<dyn Controller>> {
+        self.inputs
+            .iter()
+            .map(|runs| -> Box<dyn Controller> {
+                Box::new(ReplayController {
+                    runs: runs.clone(),
+                    cursor: Mutex::new(Cursor::at(runs, tick)),
+                })
+            })
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut file, self)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, Box<dyn Error>> {
+        let data = fs::read(path)?;
+        if data.len() < 6 || &data[..4] != MAGIC {
+            return Err("not a replay file".into());
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != VERSION {
+            return Err(format!("unsupported replay version {}", version).into());
+        }
+        let replay: Replay = bincode::deserialize(&data[6..])?;
+        if replay.inputs.len() != replay.players {
+            return Err("the number of players doesn't match the inputs".into());
+        }
+        Ok(replay)
+    }
+}
+
+impl Cursor {
+    /// The cursor after playing the ticks before `tick`.
+    fn at(runs: &[(u32, PlayerInput)], tick: u32) -> Cursor {
+        let mut cursor = Cursor::default();
+        let mut left = tick;
+        for &(ticks, input) in runs {
+            if left == 0 {
+                break;
+            }
+            cursor.current = input;
+            if left < ticks {
+                cursor.used = left;
+                return cursor;
+            }
+            cursor.run += 1;
+            left -= ticks;
+        }
+        if left > 0 {
+            // After the end of the recording.
+            cursor.current = PlayerInput::default();
+        }
+        cursor
+    }
+}
+
+impl Controller for ReplayController {
+    fn movement_status(&self) -> (f32, f32) {
+        self.cursor.lock().unwrap().current.movement
+    }
+
+    fn fire_status(&self) -> bool {
+        self.cursor.lock().unwrap().current.fire
+    }
+
+    /// The player does nothing after the end of the recording.
+    fn input(&self) -> PlayerInput {
+        let cursor = &mut *self.cursor.lock().unwrap();
+        cursor.current = match self.runs.get(cursor.run) {
+            Some(&(ticks, input)) => {
+                cursor.used += 1;
+                if cursor.used == ticks {
+                    cursor.run += 1;
+                    cursor.used = 0;
+                }
+                input
+            }
+            None => PlayerInput::default(),
+        };
+        cursor.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(rot: f32, fire: bool) -> PlayerInput {
+        PlayerInput {
+            movement: (rot, 1.0),
+            fire,
+        }
+    }
+
+    #[test]
+    fn controllers_repeat_the_recording() {
+        let mut replay = Replay::new(2, 0, None, WallColliders::Cuboids);
+        let ticks = [
+            [input(0.0, false), input(1.0, true)],
+            [input(0.0, false), input(1.0, false)],
+            [input(0.5, true), input(1.0, false)],
+            [input(0.5, true), input(-1.0, false)],
+        ];
+        for inputs in ticks.iter() {
+            replay.record(inputs);
+        }
+        assert_eq!(replay.ticks(), 4);
+        assert_eq!(replay.inputs[0].len(), 2);
+
+        let controllers = replay.controllers();
+        for inputs in ticks.iter() {
+            let played: Vec<PlayerInput> = controllers.iter().map(|c| c.input()).collect();
+            assert_eq!(played, inputs);
+        }
+        assert_eq!(controllers[0].input(), PlayerInput::default());
+    }
+
+    #[test]
+    fn controllers_start_from_any_tick() {
+        let mut replay = Replay::new(1, 0, None, WallColliders::Cuboids);
+        for tick in 0..20 {
+            replay.record(&[input((tick / 3) as f32, tick % 7 == 0)]);
+        }
+        let played: Vec<PlayerInput> = {
+            let controllers = replay.controllers();
+            (0..25).map(|_| controllers[0].input()).collect()
+        };
+        for tick in 0..25 {
+            let controllers = replay.controllers_at(tick);
+            if tick > 0 {
+                assert_eq!(controllers[0].movement_status(), played[tick as usize - 1].movement);
+            }
+            for expected in played[tick as usize..].iter() {
+                assert_eq!(&controllers[0].input(), expected, "from tick {}", tick);
+            }
+        }
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut replay = Replay::new(1, 42, Some(Maze::empty(3, 2)), WallColliders::Trimesh);
+        replay.record(&[input(0.25, true)]);
+        let path = std::env::temp_dir().join("open_tanktrouble_replay_test.ottr");
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.colliders, WallColliders::Trimesh);
+        assert_eq!(loaded.arena.unwrap().to_ascii(), Maze::empty(3, 2).to_ascii());
+        assert_eq!(loaded.inputs, replay.inputs);
+    }
+
+    #[test]
+    fn replay_reproduces_the_match() {
+        let mut simulation = Simulation::new(2, 5, None, WallColliders::Cuboids);
+        let mut replay = Replay::new(2, 5, None, WallColliders::Cuboids);
+        let mut snapshots = Vec::new();
+        for tick in 0..300u32 {
+            let inputs = [input((tick / 50 % 3) as f32 - 1.0, tick % 45 == 0), input(0.3, false)];
+            replay.record(&inputs);
+            snapshots.push(simulation.step(&inputs));
+        }
+
+        let mut simulation = replay.simulation();
+        let controllers = replay.controllers();
+        for snapshot in snapshots {
+            let inputs = simulation.poll_inputs(&controllers);
+            assert_eq!(simulation.step(&inputs), snapshot);
+        }
+    }
+}
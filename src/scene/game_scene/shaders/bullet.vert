@@ -0,0 +1,14 @@
+#version 450 core
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 trans;
+    float forecast;
+} uniforms;
+layout(location = 0) in vec2 v_pos;
+layout(location = 1) in vec2 i_pos;
+layout(location = 2) in vec2 i_vlc;
+
+void main() {
+    vec2 pos = v_pos + (i_pos + i_vlc*uniforms.forecast);
+    gl_Position = uniforms.trans * vec4(pos, 0.0, 1.0);
+}
\ No newline at end of file
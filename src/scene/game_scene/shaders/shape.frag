@@ -0,0 +1,8 @@
+#version 450 core
+
+layout(location = 0) in vec4 f_color;
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    o_color = f_color;
+}
@@ -0,0 +1,15 @@
+#version 450 core
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 trans;
+    float forecast;
+} uniforms;
+layout(location = 0) in vec2 v_pos;
+layout(location = 1) in vec4 v_color;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = v_color;
+    gl_Position = uniforms.trans * vec4(v_pos, 0.0, 1.0);
+}
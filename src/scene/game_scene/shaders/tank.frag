@@ -1,7 +1,8 @@
 #version 450 core
 
+layout(location = 0) in vec3 f_colour;
 layout(location = 0) out vec4 f_color;
 
 void main() {
-    f_color = vec4(0.0, 0.0, 0.0, 1.0);
+    f_color = vec4(f_colour, 1.0);
 }
\ No newline at end of file
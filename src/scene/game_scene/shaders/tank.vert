@@ -5,12 +5,19 @@ layout(set = 0, binding = 0) uniform Data {
     float forecast;
 } uniforms;
 layout(location = 0) in vec2 v_pos;
-layout(location = 1) in vec2 i_pos;
-layout(location = 2) in vec2 i_vlc;
-layout(location = 3) in float i_rot;
-layout(location = 4) in float i_rot_v;
+layout(location = 1) in float v_tint;
+layout(location = 2) in vec2 i_pos;
+layout(location = 3) in vec2 i_vlc;
+layout(location = 4) in float i_rot;
+layout(location = 5) in float i_rot_v;
+layout(location = 6) in vec3 i_colour;
+
+layout(location = 0) out vec3 f_colour;
+
+const vec3 DARK = vec3(0.2, 0.2, 0.2);
 
 void main() {
+    f_colour = mix(DARK, i_colour, v_tint);
     float f_rot = i_rot + i_rot_v*uniforms.forecast;
     mat2 rot = mat2(cos(f_rot), sin(f_rot), -sin(f_rot), cos(f_rot));
     vec2 pos = rot * v_pos + (i_pos + i_vlc*uniforms.forecast);
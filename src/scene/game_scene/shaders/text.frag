@@ -0,0 +1,12 @@
+#version 450 core
+
+layout(set = 1, binding = 0) uniform texture2D t_atlas;
+layout(set = 1, binding = 1) uniform sampler s_atlas;
+layout(location = 0) in vec2 f_uv;
+layout(location = 1) in vec4 f_color;
+layout(location = 0) out vec4 o_color;
+
+void main() {
+    float coverage = texture(sampler2D(t_atlas, s_atlas), f_uv).r;
+    o_color = vec4(f_color.rgb, f_color.a * coverage);
+}
@@ -0,0 +1,22 @@
+#version 450 core
+
+layout(set = 0, binding = 0) uniform Data {
+    mat4 trans;
+    float forecast;
+} uniforms;
+layout(location = 0) in vec2 v_pos;
+layout(location = 1) in vec2 i_pos;
+layout(location = 2) in vec2 i_size;
+layout(location = 3) in vec2 i_uv_min;
+layout(location = 4) in vec2 i_uv_max;
+layout(location = 5) in vec4 i_color;
+
+layout(location = 0) out vec2 f_uv;
+layout(location = 1) out vec4 f_color;
+
+void main() {
+    // The atlas is stored from top to bottom.
+    f_uv = mix(i_uv_min, i_uv_max, vec2(v_pos.x, 1.0 - v_pos.y));
+    f_color = i_color;
+    gl_Position = uniforms.trans * vec4(i_pos + v_pos * i_size, 0.0, 1.0);
+}
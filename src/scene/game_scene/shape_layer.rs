@@ -0,0 +1,171 @@
+use std::ops::{Deref, DerefMut};
+
+use cgmath::num_traits::FloatConst;
+use wgpu::util::DeviceExt;
+
+use crate::scene::render_layer::{BasicLayer, VertexAndIndexes};
+
+/// Number of segments used to approximate a circle.
+const CIRCLE_SEGMENTS: usize = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColourVertex {
+    position: [f32; 2],
+    colour: [f32; 4],
+}
+
+/// Flat coloured shapes, built on the updater thread and drawn in one call.
+#[derive(Default)]
+pub struct ShapeData {
+    pub vertex: Vec<ColourVertex>,
+    pub index: Vec<u32>,
+}
+
+pub struct ShapeLayer(BasicLayer<VertexAndIndexes>);
+
+impl ShapeData {
+    /// A rectangle of the size around the center.
+    pub fn rect(&mut self, center: [f32; 2], size: [f32; 2], colour: [f32; 4]) {
+        let (x, y) = (size[0] / 2.0, size[1] / 2.0);
+        self.polygon(
+            &[
+                [center[0] - x, center[1] - y],
+                [center[0] + x, center[1] - y],
+                [center[0] + x, center[1] + y],
+                [center[0] - x, center[1] + y],
+            ],
+            colour,
+        );
+    }
+
+    /// The border of a rectangle, `width` is inside the size.
+    pub fn frame(&mut self, center: [f32; 2], size: [f32; 2], width: f32, colour: [f32; 4]) {
+        let (x, y) = ((size[0] - width) / 2.0, (size[1] - width) / 2.0);
+        self.rect([center[0], center[1] + y], [size[0], width], colour);
+        self.rect([center[0], center[1] - y], [size[0], width], colour);
+        self.rect([center[0] + x, center[1]], [width, size[1]], colour);
+        self.rect([center[0] - x, center[1]], [width, size[1]], colour);
+    }
+
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, colour: [f32; 4]) {
+        let points: Vec<[f32; 2]> = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                let (sin, cos) = (2.0 * f32::PI() * i as f32 / CIRCLE_SEGMENTS as f32).sin_cos();
+                [center[0] + cos * radius, center[1] + sin * radius]
+            })
+            .collect();
+        self.polygon(&points, colour);
+    }
+
+    /// A convex polygon, points are in either winding order.
+    pub fn polygon(&mut self, points: &[[f32; 2]], colour: [f32; 4]) {
+        let offset = self.vertex.len() as u32;
+        self.vertex.extend(
+            points
+                .iter()
+                .map(|&position| ColourVertex { position, colour }),
+        );
+        for i in 1..points.len().saturating_sub(1) as u32 {
+            self.index.extend_from_slice(&[offset, offset + i, offset + i + 1]);
+        }
+    }
+}
+
+impl Deref for ShapeLayer {
+    type Target = BasicLayer<VertexAndIndexes>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for ShapeLayer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl ShapeLayer {
+    pub fn new(
+        device: &wgpu::Device,
+        fragment_format: wgpu::ColorTargetState,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let pipeline = Self::pipeline(device, fragment_format, uniform_bind_group_layout);
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shape Vertex Buffer"),
+            contents: &[],
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shape Index Buffer"),
+            contents: &[],
+            usage: wgpu::BufferUsage::INDEX,
+        });
+
+        ShapeLayer(BasicLayer {
+            pipeline,
+            buffer: VertexAndIndexes {
+                vertex: vertex_buffer,
+                index: index_buffer,
+                index_num: 0,
+            },
+        })
+    }
+
+    fn pipeline(
+        device: &wgpu::Device,
+        fragment_format: wgpu::ColorTargetState,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let vs_module =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/shape.vert.spv"));
+        let fs_module =
+            device.create_shader_module(&wgpu::include_spirv!("shaders/shape.frag.spv"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shape Layer Pipeline Layout"),
+                bind_group_layouts: &[uniform_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shape Layer Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ColourVertex>() as wgpu::BufferAddress,
+                    step_mode: wgpu::InputStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float2, 1 => Float4],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[fragment_format],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    pub fn update_shapes(&mut self, device: &wgpu::Device, shapes: ShapeData) {
+        self.buffer.vertex = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shape Vertex Buffer"),
+            contents: bytemuck::cast_slice(&shapes.vertex),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        self.buffer.index = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shape Index Buffer"),
+            contents: bytemuck::cast_slice(&shapes.index),
+            usage: wgpu::BufferUsage::INDEX,
+        });
+        self.buffer.index_num = shapes.index.len();
+    }
+}
@@ -0,0 +1,827 @@
+//! The game rules and the physic world, without any window, ticker or thread.
+use crossbeam_channel::{Receiver, unbounded};
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rapier2d::{
+    dynamics::{
+        CoefficientCombineRule, IntegrationParameters, JointSet, RigidBodyBuilder, RigidBodyHandle,
+        RigidBodySet,
+    },
+    geometry::{
+        BroadPhase, ColliderBuilder, ColliderHandle, ColliderSet, ContactEvent, NarrowPhase, Ray,
+    },
+    math::{Point, Rotation},
+    na::{Rotation2, Vector2},
+    pipeline::{ChannelEventCollector, PhysicsPipeline, QueryPipeline},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::input::{Controller, PlayerInput, TankPose, WorldView};
+use crate::scene::maze::{spawn, Maze};
+
+use super::{
+    BULLET_GROUP, BULLET_LIFETIME, BULLET_RADIUS, BULLET_SPEED, MAX_BULLETS_PER_TANK, MAZE_GROUP,
+    PHYSICAL_DT, ROUND_END_DELAY, TANK_GROUP, WallColliders,
+};
+
+/// A match of some players, stepped one physic tick at a time.
+/// The same seed and the same inputs always give the same snapshots.
+pub struct Simulation {
+    physical: PhysicalStatus,
+    players: usize,
+    /// All random things in the match come from it, seeded with the seed of the match.
+    rng: ChaCha8Rng,
+    /// The fixed maze of every round, random ones are generated if it's None.
+    arena: Option<Maze>,
+    colliders: WallColliders,
+    /// The maze of current round.
+    maze: Maze,
+    scores: Vec<u32>,
+    round: u32,
+    /// The seq_number when there is no more than one tank left.
+    round_over_since: Option<u32>,
+}
+
+/// Everything needed to draw the world after a tick.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub seq_number: u32,
+    /// Starts from 1, a new maze is used when it changes.
+    pub round: u32,
+    pub scores: Vec<u32>,
+    /// All tanks in the order of players, including destroyed ones.
+    pub tanks: Vec<TankState>,
+    pub bullets: Vec<BulletState>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TankState {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    pub alive: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BulletState {
+    /// Index of the tank which fired this bullet.
+    pub owner: usize,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+}
+
+struct PhysicalStatus {
+    tanks: Vec<PhysicTank>,
+    bullets: Vec<PhysicBullet>,
+    seq_number: u32,
+
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+    joint_set: JointSet,
+
+    event_collector: ChannelEventCollector,
+    contact_event_chan: Receiver<ContactEvent>,
+}
+
+/// Everything in the physic world at a tick, see [`PhysicalStatus::save`].
+#[derive(Clone)]
+struct PhysicalSnapshot {
+    tanks: Vec<PhysicTank>,
+    bullets: Vec<PhysicBullet>,
+    seq_number: u32,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+}
+
+/// The whole state of a [`Simulation`] at a tick, restoring it goes back to the tick.
+#[derive(Clone)]
+pub struct SimulationState {
+    physical: PhysicalSnapshot,
+    rng: ChaCha8Rng,
+    maze: Maze,
+    scores: Vec<u32>,
+    round: u32,
+    round_over_since: Option<u32>,
+}
+
+#[derive(Clone)]
+struct PhysicTank {
+    rigid_body_handle: RigidBodyHandle,
+    collider_handle: ColliderHandle,
+    /// A destroyed tank has no rigid body until next round.
+    alive: bool,
+    /// Fire status of last tick, a bullet is only fired when the button goes down.
+    fire_pressed: bool,
+}
+
+#[derive(Clone)]
+struct PhysicBullet {
+    /// Index of the tank which fired this bullet.
+    owner: usize,
+    rigid_body_handle: RigidBodyHandle,
+    collider_handle: ColliderHandle,
+    /// The seq_number when this bullet is fired.
+    born: u32,
+}
+
+impl Simulation {
+    pub fn new(
+        players: usize,
+        seed: u64,
+        arena: Option<Maze>,
+        colliders: WallColliders,
+    ) -> Simulation {
+        let mut simulation = Simulation {
+            physical: PhysicalStatus::new(),
+            players,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            arena,
+            colliders,
+            // Replaced in reset()
+            maze: Maze::closed(1, 1),
+            scores: Vec::new(),
+            round: 0,
+            round_over_since: None,
+        };
+        simulation.reset(seed);
+        simulation
+    }
+
+    /// Restart the match from the first round with another seed.
+    pub fn reset(&mut self, seed: u64) {
+        self.physical = PhysicalStatus::new();
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.scores = vec![0; self.players];
+        self.round = 1;
+        self.round_over_since = None;
+        self.new_round();
+    }
+
+    pub fn players(&self) -> usize {
+        self.players
+    }
+
+    pub fn maze(&self) -> &Maze {
+        &self.maze
+    }
+
+    /// Let every controller see the world, then collect their input of the next tick.
+    pub fn poll_inputs(&self, controllers: &[Box<dyn Controller>]) -> Vec<PlayerInput> {
+        let tanks = self.tank_poses();
+        controllers
+            .iter()
+            .enumerate()
+            .map(|(me, controller)| {
+                controller.observe(&WorldView {
+                    maze: &self.maze,
+                    tanks: &tanks,
+                    me,
+                });
+                controller.input()
+            })
+            .collect()
+    }
+
+    /// Run one physic tick, players without an input in `inputs` do nothing.
+    pub fn step(&mut self, inputs: &[PlayerInput]) -> Snapshot {
+        self.physical.update_tick(inputs);
+
+        let seq_number = self.physical.seq_number;
+        let mut survivors = self.physical.tanks.iter().enumerate().filter(|(_, t)| t.alive);
+        match self.round_over_since {
+            None if survivors.clone().count() <= 1 => {
+                self.round_over_since = Some(seq_number);
+            }
+            Some(since) if seq_number - since >= ROUND_END_DELAY => {
+                match survivors.next() {
+                    Some((winner, _)) => {
+                        self.scores[winner] += 1;
+                        info!("Round {} winner: player {}", self.round, winner);
+                    }
+                    None => info!("Round {} draw", self.round),
+                }
+                info!("Scores: {:?}", self.scores);
+                self.round += 1;
+                self.round_over_since = None;
+                self.new_round();
+            }
+            _ => {}
+        }
+        self.snapshot()
+    }
+
+    pub fn save(&self) -> SimulationState {
+        SimulationState {
+            physical: self.physical.save(),
+            rng: self.rng.clone(),
+            maze: self.maze.clone(),
+            scores: self.scores.clone(),
+            round: self.round,
+            round_over_since: self.round_over_since,
+        }
+    }
+
+    /// Go back to the saved tick, stepping the same inputs again gives the same snapshots.
+    pub fn restore(&mut self, state: &SimulationState) {
+        self.physical.restore(&state.physical);
+        self.rng = state.rng.clone();
+        self.maze = state.maze.clone();
+        self.scores = state.scores.clone();
+        self.round = state.round;
+        self.round_over_since = state.round_over_since;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            seq_number: self.physical.seq_number,
+            round: self.round,
+            scores: self.scores.clone(),
+            tanks: self.physical.tank_states(),
+            bullets: self.physical.bullet_states(),
+        }
+    }
+
+    pub fn tank_poses(&self) -> Vec<TankPose> {
+        self.physical.tank_states().iter().map(TankPose::from).collect()
+    }
+
+    /// Clear the world, then generate a new maze and respawn all tanks.
+    fn new_round(&mut self) {
+        self.physical.clear();
+        self.maze = match &self.arena {
+            Some(arena) => arena.clone(),
+            None => Maze::new(self.players, &mut self.rng),
+        };
+        self.physical.add_maze(&self.maze, self.colliders);
+
+        let spawns: Vec<(Point<f32>, f32)> =
+            spawn::spawn_points(&self.maze, self.players, &mut self.rng)
+                .into_iter()
+                .map(|(cell, angle)| (self.maze.cell_center(cell), angle))
+                .collect();
+        self.physical.spawn_tanks(&spawns);
+    }
+}
+
+impl Snapshot {
+    /// What controllers see of the tanks, for those driving a remote simulation.
+    pub fn tank_poses(&self) -> Vec<TankPose> {
+        self.tanks.iter().map(TankPose::from).collect()
+    }
+}
+
+impl From<&TankState> for TankPose {
+    fn from(tank: &TankState) -> Self {
+        TankPose {
+            position: tank.position.into(),
+            rotation: tank.rotation,
+            alive: tank.alive,
+        }
+    }
+}
+
+impl PhysicalStatus {
+    fn new() -> PhysicalStatus {
+        let (intersection_event_sender, _) = unbounded();
+        let (contact_event_sender, contact_event_chan) = unbounded();
+        let mut physical = PhysicalStatus {
+            tanks: Vec::new(),
+            bullets: Vec::new(),
+            seq_number: 0,
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters::default(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            rigid_body_set: RigidBodySet::new(),
+            collider_set: ColliderSet::new(),
+            joint_set: JointSet::new(),
+            event_collector: ChannelEventCollector::new(
+                intersection_event_sender,
+                contact_event_sender,
+            ),
+            contact_event_chan,
+        };
+        physical.integration_parameters.dt = PHYSICAL_DT;
+        physical
+    }
+
+    fn update_tick(&mut self, inputs: &[PlayerInput]) {
+        let gravity = Vector2::new(0.0, 0.0);
+
+        // Apply the control to the tank.
+        for (tank, input) in self.tanks.iter_mut().zip(inputs) {
+            if !tank.alive {
+                continue;
+            }
+            let (rot, acl) = input.movement;
+            let right_body = &mut self.rigid_body_set[tank.rigid_body_handle];
+            let rotation = &Rotation2::from(right_body.position().rotation);
+            right_body.apply_force(rotation * Vector2::new(0.0, acl * 30.0), true);
+            right_body.apply_torque(-rot * 40.0, true);
+            right_body.set_linvel(
+                Rotation::new(right_body.angvel() * PHYSICAL_DT) * right_body.linvel(),
+                true,
+            );
+        }
+        self.fire_bullets(inputs);
+        self.remove_expired_bullets();
+
+        self.pipeline.step(
+            &gravity,
+            &self.integration_parameters,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.rigid_body_set,
+            &mut self.collider_set,
+            &mut self.joint_set,
+            None,
+            None,
+            &self.event_collector,
+        );
+        self.handle_contact_events();
+        // Keep bullets flying at a constant speed, whatever they hit.
+        for bullet in self.bullets.iter() {
+            let right_body = &mut self.rigid_body_set[bullet.rigid_body_handle];
+            if let Some(direction) = right_body.linvel().try_normalize(f32::EPSILON) {
+                right_body.set_linvel(direction * BULLET_SPEED, false);
+            }
+        }
+        // Increase simulate sequence number.
+        self.seq_number += 1;
+    }
+
+    /// Destroy tanks hit by bullets, the bullet disappears as well.
+    fn handle_contact_events(&mut self) {
+        while let Ok(event) = self.contact_event_chan.try_recv() {
+            let (collider1, collider2) = match event {
+                ContactEvent::Started(collider1, collider2) => (collider1, collider2),
+                ContactEvent::Stopped(..) => continue,
+            };
+            let hit = self.bullets.iter().position(|b| {
+                b.collider_handle == collider1 || b.collider_handle == collider2
+            });
+            let tank = self.tanks.iter().position(|t| {
+                t.alive && (t.collider_handle == collider1 || t.collider_handle == collider2)
+            });
+            if let (Some(bullet), Some(tank)) = (hit, tank) {
+                let bullet = self.bullets.remove(bullet);
+                self.rigid_body_set.remove(
+                    bullet.rigid_body_handle,
+                    &mut self.collider_set,
+                    &mut self.joint_set,
+                );
+                let tank_handle = self.tanks[tank].rigid_body_handle;
+                self.rigid_body_set
+                    .remove(tank_handle, &mut self.collider_set, &mut self.joint_set);
+                self.tanks[tank].alive = false;
+                info!("Tank {} is destroyed by tank {}", tank, bullet.owner);
+            }
+        }
+    }
+
+    /// Fire a bullet from the barrel of every tank whose fire button is just pressed.
+    fn fire_bullets(&mut self, inputs: &[PlayerInput]) {
+        for (owner, (tank, input)) in self.tanks.iter_mut().zip(inputs).enumerate() {
+            let fire_pressed = input.fire;
+            let just_pressed = fire_pressed && !tank.fire_pressed;
+            tank.fire_pressed = fire_pressed;
+            if !tank.alive {
+                continue;
+            }
+            let bullet_count = self.bullets.iter().filter(|b| b.owner == owner).count();
+            if !just_pressed || bullet_count >= MAX_BULLETS_PER_TANK {
+                continue;
+            }
+
+            let tank_position = self.rigid_body_set[tank.rigid_body_handle].position();
+            let direction = Rotation2::from(tank_position.rotation) * Vector2::new(0.0, 1.0);
+            let (position, velocity) = launch_bullet(
+                &self.rigid_body_set,
+                &self.collider_set,
+                tank_position.translation.vector,
+                direction,
+            );
+            let right_body = RigidBodyBuilder::new_dynamic()
+                .translation(position.x, position.y)
+                .linvel(velocity.x, velocity.y)
+                .can_sleep(false)
+                .build();
+            let collider = ColliderBuilder::ball(BULLET_RADIUS)
+                .restitution(1.0)
+                .restitution_combine_rule(CoefficientCombineRule::Max)
+                .friction(0.0)
+                .friction_combine_rule(CoefficientCombineRule::Min)
+                .density(0.1)
+                .collision_groups(BULLET_GROUP)
+                .build();
+            let rigid_body_handle = self.rigid_body_set.insert(right_body);
+            let collider_handle =
+                self.collider_set
+                    .insert(collider, rigid_body_handle, &mut self.rigid_body_set);
+            self.bullets.push(PhysicBullet {
+                owner,
+                rigid_body_handle,
+                collider_handle,
+                born: self.seq_number,
+            });
+        }
+    }
+
+    fn remove_expired_bullets(&mut self) {
+        let seq_number = self.seq_number;
+        let rigid_body_set = &mut self.rigid_body_set;
+        let collider_set = &mut self.collider_set;
+        let joint_set = &mut self.joint_set;
+        self.bullets.retain(|bullet| {
+            let alive = seq_number - bullet.born < BULLET_LIFETIME;
+            if !alive {
+                rigid_body_set.remove(bullet.rigid_body_handle, collider_set, joint_set);
+            }
+            alive
+        });
+    }
+
+    fn tank_states(&self) -> Vec<TankState> {
+        self.tanks
+            .iter()
+            .map(|tank| match self.rigid_body_set.get(tank.rigid_body_handle) {
+                Some(rigid_body) if tank.alive => {
+                    let position = rigid_body.position();
+                    let velocity = rigid_body.linvel();
+                    TankState {
+                        position: position.translation.vector.into(),
+                        velocity: [velocity.x, velocity.y],
+                        rotation: position.rotation.angle(),
+                        angular_velocity: rigid_body.angvel(),
+                        alive: true,
+                    }
+                }
+                _ => TankState::default(),
+            })
+            .collect()
+    }
+
+    fn bullet_states(&self) -> Vec<BulletState> {
+        self.bullets
+            .iter()
+            .map(|bullet| {
+                let rigid_body = &self.rigid_body_set[bullet.rigid_body_handle];
+                let velocity = rigid_body.linvel();
+                BulletState {
+                    owner: bullet.owner,
+                    position: rigid_body.position().translation.vector.into(),
+                    velocity: [velocity.x, velocity.y],
+                }
+            })
+            .collect()
+    }
+
+    fn save(&self) -> PhysicalSnapshot {
+        PhysicalSnapshot {
+            tanks: self.tanks.clone(),
+            bullets: self.bullets.clone(),
+            seq_number: self.seq_number,
+            broad_phase: self.broad_phase.clone(),
+            narrow_phase: self.narrow_phase.clone(),
+            rigid_body_set: self.rigid_body_set.clone(),
+            collider_set: self.collider_set.clone(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: &PhysicalSnapshot) {
+        let snapshot = snapshot.clone();
+        self.tanks = snapshot.tanks;
+        self.bullets = snapshot.bullets;
+        self.seq_number = snapshot.seq_number;
+        self.broad_phase = snapshot.broad_phase;
+        self.narrow_phase = snapshot.narrow_phase;
+        self.rigid_body_set = snapshot.rigid_body_set;
+        self.collider_set = snapshot.collider_set;
+        // There are no joints in the match.
+        self.joint_set = JointSet::new();
+        // Events of the abandoned ticks
+        while self.contact_event_chan.try_recv().is_ok() {}
+    }
+
+    /// Remove everything in the physic world.
+    fn clear(&mut self) {
+        self.bullets.clear();
+        self.broad_phase = BroadPhase::new();
+        self.narrow_phase = NarrowPhase::new();
+        self.rigid_body_set = RigidBodySet::new();
+        self.collider_set = ColliderSet::new();
+        self.joint_set = JointSet::new();
+        while self.contact_event_chan.try_recv().is_ok() {}
+        for tank in self.tanks.iter_mut() {
+            tank.alive = false;
+        }
+    }
+
+    fn create_tank_body(
+        &mut self,
+        position: Point<f32>,
+        angle: f32,
+    ) -> (RigidBodyHandle, ColliderHandle) {
+        let right_body = RigidBodyBuilder::new_dynamic()
+            .translation(position.x, position.y)
+            .rotation(angle)
+            .can_sleep(true)
+            .mass(0.9)
+            .linear_damping(10.0)
+            .principal_angular_inertia(0.8)
+            .angular_damping(10.0)
+            .build();
+        let collider = ColliderBuilder::cuboid(0.2, 0.25)
+            .collision_groups(TANK_GROUP)
+            .build();
+        let rigid_body_handle = self.rigid_body_set.insert(right_body);
+        let collider_handle =
+            self.collider_set
+                .insert(collider, rigid_body_handle, &mut self.rigid_body_set);
+        (rigid_body_handle, collider_handle)
+    }
+
+    /// Put new bodies into the world for all tanks.
+    fn spawn_tanks(&mut self, spawns: &[(Point<f32>, f32)]) {
+        let mut tanks = Vec::with_capacity(spawns.len());
+        for (index, &(position, angle)) in spawns.iter().enumerate() {
+            let (rigid_body_handle, collider_handle) = self.create_tank_body(position, angle);
+            tanks.push(PhysicTank {
+                rigid_body_handle,
+                collider_handle,
+                alive: true,
+                // Holding the fire button across rounds doesn't fire.
+                fire_pressed: self.tanks.get(index).map_or(false, |t| t.fire_pressed),
+            });
+        }
+        self.tanks = tanks;
+    }
+
+    fn add_maze(&mut self, maze: &Maze, colliders: WallColliders) {
+        let right_body = RigidBodyBuilder::new_static().build();
+        let rigid_body_handle = self.rigid_body_set.insert(right_body);
+        let shapes = match colliders {
+            WallColliders::Cuboids => maze
+                .wall_rects()
+                .iter()
+                .map(|rect| {
+                    let (hx, hy) = rect.half_extents();
+                    let center = rect.center();
+                    ColliderBuilder::cuboid(hx, hy).translation(center.x, center.y)
+                })
+                .collect(),
+            WallColliders::Trimesh => {
+                let (vertices, indices) = maze.triangle_mesh();
+                vec![ColliderBuilder::trimesh(vertices, indices)]
+            }
+        };
+        for shape in shapes {
+            let collider = shape.collision_groups(MAZE_GROUP).build();
+            self.collider_set
+                .insert(collider, rigid_body_handle, &mut self.rigid_body_set);
+        }
+    }
+}
+
+/// Where a bullet fired from a tank at `origin` towards `direction` starts, and its velocity.
+/// It starts at the muzzle, unless the barrel pokes into a wall, then it bounces off the wall.
+fn launch_bullet(
+    rigid_body_set: &RigidBodySet,
+    collider_set: &ColliderSet,
+    origin: Vector2<f32>,
+    direction: Vector2<f32>,
+) -> (Vector2<f32>, Vector2<f32>) {
+    let muzzle = 0.25 + BULLET_RADIUS * 2.0;
+    let velocity = direction * BULLET_SPEED;
+    let mut query_pipeline = QueryPipeline::new();
+    query_pipeline.update(rigid_body_set, collider_set);
+    let ray = Ray::new(origin.into(), direction);
+    // Only walls, the ray starts inside the tank firing it.
+    let walls = BULLET_GROUP.with_mask(0b001);
+    let hit = query_pipeline.cast_ray_and_get_normal(
+        collider_set,
+        &ray,
+        muzzle + BULLET_RADIUS,
+        true,
+        walls,
+    );
+    match hit {
+        None => (origin + direction * muzzle, velocity),
+        Some((_, hit)) => {
+            let position = ray.point_at(hit.toi).coords + hit.normal * BULLET_RADIUS;
+            let velocity = velocity - hit.normal * (2.0 * velocity.dot(&hit.normal));
+            (position, velocity)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use rapier2d::math::Isometry;
+
+    use crate::scene::maze::{Cell, ARENA};
+
+    use super::*;
+
+    const IDLE: PlayerInput = PlayerInput {
+        movement: (0.0, 0.0),
+        fire: false,
+    };
+    const FIRE: PlayerInput = PlayerInput {
+        movement: (0.0, 0.0),
+        fire: true,
+    };
+
+    /// Move the tank of `player` into the cell, the barrel points to `rotation`.
+    fn place_tank(simulation: &mut Simulation, player: usize, cell: Cell, rotation: f32) {
+        let position = simulation.maze().cell_center(cell);
+        let physical = &mut simulation.physical;
+        let rigid_body = &mut physical.rigid_body_set[physical.tanks[player].rigid_body_handle];
+        rigid_body.set_position(Isometry::new(position.coords, rotation), true);
+    }
+
+    /// Some meaningless but reproducible inputs.
+    fn scripted_inputs(tick: u32, players: usize) -> Vec<PlayerInput> {
+        (0..players as u32)
+            .map(|player| PlayerInput {
+                movement: (((tick / 40 + player) % 3) as f32 - 1.0, 1.0),
+                fire: (tick + player * 7) % 30 == 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_snapshots() {
+        let mut simulation1 = Simulation::new(3, 7, None, WallColliders::Cuboids);
+        let mut simulation2 = Simulation::new(3, 7, None, WallColliders::Cuboids);
+        for tick in 0..600 {
+            let inputs = scripted_inputs(tick, 3);
+            assert_eq!(simulation1.step(&inputs), simulation2.step(&inputs), "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn reset_restarts_the_match() {
+        let mut simulation = Simulation::new(2, 1, None, WallColliders::Cuboids);
+        let first: Vec<Snapshot> = (0..100)
+            .map(|tick| simulation.step(&scripted_inputs(tick, 2)))
+            .collect();
+        simulation.reset(2);
+        simulation.reset(1);
+        for (tick, snapshot) in first.iter().enumerate() {
+            assert_eq!(&simulation.step(&scripted_inputs(tick as u32, 2)), snapshot);
+        }
+    }
+
+    #[test]
+    fn restore_goes_back_to_the_tick() {
+        let mut simulation = Simulation::new(3, 4, None, WallColliders::Cuboids);
+        for tick in 0..200 {
+            simulation.step(&scripted_inputs(tick, 3));
+        }
+        let state = simulation.save();
+        let first: Vec<Snapshot> = (200..800)
+            .map(|tick| simulation.step(&scripted_inputs(tick, 3)))
+            .collect();
+        simulation.restore(&state);
+        for (tick, snapshot) in (200..800).zip(first.iter()) {
+            assert_eq!(&simulation.step(&scripted_inputs(tick, 3)), snapshot, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn tanks_spawn_in_different_cells() {
+        for seed in 0..10 {
+            let simulation = Simulation::new(8, seed, None, WallColliders::Cuboids);
+            let snapshot = simulation.snapshot();
+            assert_eq!(snapshot.tanks.len(), 8);
+            let mut cells: Vec<_> = snapshot
+                .tanks
+                .iter()
+                .map(|tank| simulation.maze().cell_at(&tank.position.into()).unwrap())
+                .collect();
+            cells.sort_unstable();
+            cells.dedup();
+            assert_eq!(cells.len(), 8, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn holding_fire_button_fires_once() {
+        let mut simulation = Simulation::new(2, 3, None, WallColliders::Cuboids);
+        let inputs = [
+            PlayerInput {
+                movement: (0.0, 0.0),
+                fire: true,
+            },
+            PlayerInput::default(),
+        ];
+        assert_eq!(simulation.step(&inputs).bullets.len(), 1);
+        for _ in 0..30 {
+            // The bullet may bounce back and destroy the tank itself.
+            assert!(simulation.step(&inputs).bullets.len() <= 1);
+        }
+    }
+
+    #[test]
+    fn bullets_bounce_off_walls() {
+        let arena = Maze::from_ascii(ARENA).unwrap();
+        let mut simulation = Simulation::new(2, 5, Some(arena), WallColliders::Cuboids);
+        // Towards the top wall, then it bounces off the right wall.
+        place_tank(&mut simulation, 0, (1, 1), (-0.6f32).atan2(0.8));
+        place_tank(&mut simulation, 1, (0, 0), 0.0);
+        let mut velocities: Vec<[f32; 2]> = Vec::new();
+        for tick in 0..60 {
+            let inputs = [if tick == 0 { FIRE } else { IDLE }, IDLE];
+            let velocity = simulation.step(&inputs).bullets[0].velocity;
+            let velocity = [velocity[0].round(), velocity[1].round()];
+            if velocities.last() != Some(&velocity) {
+                velocities.push(velocity);
+            }
+        }
+        assert_eq!(velocities[..3], [[3.0, 4.0], [3.0, -4.0], [-3.0, -4.0]]);
+    }
+
+    #[test]
+    fn barrel_in_a_wall_fires_backwards() {
+        let arena = Maze::from_ascii(ARENA).unwrap();
+        let mut simulation = Simulation::new(2, 5, Some(arena), WallColliders::Cuboids);
+        place_tank(&mut simulation, 1, (0, 0), 0.0);
+        // Facing down in (1, 1), the front of the tank almost touches the wall below,
+        // whose top side is at y = 1 / 16.
+        let physical = &mut simulation.physical;
+        let rigid_body = &mut physical.rigid_body_set[physical.tanks[0].rigid_body_handle];
+        rigid_body.set_position(Isometry::new(Vector2::new(0.0, 0.32), PI), true);
+        physical.fire_bullets(&[FIRE, IDLE]);
+        let bullet = physical.bullet_states()[0];
+        assert!(bullet.position[1] - BULLET_RADIUS >= 1.0 / 16.0 - f32::EPSILON);
+        assert!(bullet.velocity[0].abs() < 1e-4);
+        assert!((bullet.velocity[1] - BULLET_SPEED).abs() < 1e-4);
+    }
+
+    #[test]
+    fn bullets_disappear_after_their_lifetime() {
+        let closed = Maze::closed(3, 1);
+        let mut simulation = Simulation::new(2, 5, Some(closed), WallColliders::Cuboids);
+        place_tank(&mut simulation, 0, (1, 0), 0.3);
+        place_tank(&mut simulation, 1, (2, 0), 0.0);
+        assert_eq!(simulation.step(&[FIRE, IDLE]).bullets.len(), 1);
+        // Leave the bullet alone in its closed cell.
+        place_tank(&mut simulation, 0, (0, 0), 0.0);
+        for tick in 1..BULLET_LIFETIME {
+            assert_eq!(simulation.step(&[IDLE, IDLE]).bullets.len(), 1, "tick {}", tick);
+        }
+        assert!(simulation.step(&[IDLE, IDLE]).bullets.is_empty());
+    }
+
+    #[test]
+    fn tanks_have_limited_bullets() {
+        let arena = Maze::from_ascii(ARENA).unwrap();
+        let mut simulation = Simulation::new(2, 5, Some(arena), WallColliders::Cuboids);
+        // Along the top row of the arena.
+        place_tank(&mut simulation, 0, (0, 1), -PI / 2.0);
+        place_tank(&mut simulation, 1, (1, 0), 0.0);
+        for shot in 0..=MAX_BULLETS_PER_TANK {
+            simulation.step(&[FIRE, IDLE]);
+            let snapshot = simulation.step(&[IDLE, IDLE]);
+            assert_eq!(snapshot.bullets.len(), (shot + 1).min(MAX_BULLETS_PER_TANK));
+        }
+    }
+
+    #[test]
+    fn hit_tank_loses_the_round() {
+        let open = Maze::empty(3, 1);
+        let mut simulation = Simulation::new(2, 5, Some(open), WallColliders::Cuboids);
+        // Face to face at both ends of the arena.
+        place_tank(&mut simulation, 0, (0, 0), -PI / 2.0);
+        place_tank(&mut simulation, 1, (2, 0), PI / 2.0);
+        let mut snapshot = simulation.step(&[FIRE, IDLE]);
+        for _ in 0..90 {
+            if !snapshot.tanks[1].alive {
+                break;
+            }
+            snapshot = simulation.step(&[IDLE, IDLE]);
+        }
+        assert!(snapshot.tanks[0].alive);
+        assert!(!snapshot.tanks[1].alive);
+        assert!(snapshot.bullets.is_empty());
+        for _ in 1..ROUND_END_DELAY {
+            snapshot = simulation.step(&[IDLE, IDLE]);
+        }
+        assert_eq!((snapshot.round, &snapshot.scores[..]), (1, &[0, 0][..]));
+        snapshot = simulation.step(&[IDLE, IDLE]);
+        assert_eq!((snapshot.round, &snapshot.scores[..]), (2, &[1, 0][..]));
+        assert!(snapshot.tanks.iter().all(|tank| tank.alive));
+    }
+}
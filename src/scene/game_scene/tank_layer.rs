@@ -1,20 +1,27 @@
 use std::ops::{Deref, DerefMut};
 
+use cgmath::num_traits::FloatConst;
 use wgpu::util::DeviceExt;
 
 use super::super::render_layer::{BasicLayer, VertexAndInstances};
-use super::Vertex;
 
+/// Half of the width and the length of the tank, the same as its collider. It faces +y.
 const A: f32 = 0.2;
 const B: f32 = 0.25;
-const TANK_VERTICES: &[Vertex] = &[
-    Vertex::new(-A, -B),
-    Vertex::new(A, -B),
-    Vertex::new(A, B),
-    Vertex::new(-A, -B),
-    Vertex::new(A, B),
-    Vertex::new(-A, B),
-];
+/// How far the barrel reaches, bullets are fired from its end.
+const BARREL: f32 = 0.33;
+/// Number of segments of the turret, a bit darker than the body.
+const TURRET_SEGMENTS: usize = 8;
+const TURRET_RADIUS: f32 = 0.1;
+const TURRET_TINT: f32 = 0.7;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TankVertex {
+    position: [f32; 2],
+    /// 0 is the dark of treads and the barrel, 1 is the colour of the tank.
+    tint: f32,
+}
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -23,6 +30,7 @@ pub struct TankInstance {
     pub velocity: [f32; 2],
     pub rotation: f32,
     pub rotation_v: f32,
+    pub colour: [f32; 3],
 }
 
 pub struct TankLayer(BasicLayer<VertexAndInstances>);
@@ -49,9 +57,10 @@ impl TankLayer {
     ) -> Self {
         let pipeline = Self::pipeline(device, fragment_format, uniform_bind_group_layout);
 
+        let vertices = tank_vertices();
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Tank Vertex Buffer"),
-            contents: bytemuck::cast_slice(TANK_VERTICES),
+            contents: bytemuck::cast_slice(&vertices),
             usage: wgpu::BufferUsage::VERTEX,
         });
 
@@ -65,9 +74,10 @@ impl TankLayer {
             pipeline,
             buffer: VertexAndInstances {
                 vertex: vertex_buffer,
-                vertex_num: TANK_VERTICES.len() as _,
+                vertex_num: vertices.len(),
                 instance: instance_buffer,
                 instance_num: 0,
+                instance_capacity: 0,
             },
         })
     }
@@ -95,14 +105,16 @@ impl TankLayer {
                 entry_point: "main",
                 buffers: &[
                     wgpu::VertexBufferLayout {
-                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        array_stride: std::mem::size_of::<TankVertex>() as wgpu::BufferAddress,
                         step_mode: wgpu::InputStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![0 => Float2],
+                        attributes: &wgpu::vertex_attr_array![0 => Float2, 1 => Float],
                     },
                     wgpu::VertexBufferLayout {
                         array_stride: std::mem::size_of::<TankInstance>() as wgpu::BufferAddress,
                         step_mode: wgpu::InputStepMode::Instance,
-                        attributes: &wgpu::vertex_attr_array![1 => Float2, 2 => Float2, 3 => Float, 4 => Float],
+                        attributes: &wgpu::vertex_attr_array![
+                            2 => Float2, 3 => Float2, 4 => Float, 5 => Float, 6 => Float3
+                        ],
                     }
                 ],
             },
@@ -123,17 +135,66 @@ impl TankLayer {
         queue: &wgpu::Queue,
         instances: Vec<TankInstance>,
     ) {
-        if self.buffer.instance_num < instances.len() {
+        if self.buffer.instance_capacity < instances.len() {
             // Recreate buffer
             self.buffer.instance = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Instance Buffer"),
                 contents: bytemuck::cast_slice(&instances),
                 usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
             });
-            self.buffer.instance_num = instances.len();
-        } else {
+            self.buffer.instance_capacity = instances.len();
+        } else if !instances.is_empty() {
             // Just send to the existing buffer
             queue.write_buffer(&self.buffer.instance, 0, bytemuck::cast_slice(&instances));
         }
+        self.buffer.instance_num = instances.len();
+    }
+}
+
+impl TankVertex {
+    const fn new(x: f32, y: f32, tint: f32) -> TankVertex {
+        TankVertex {
+            position: [x, y],
+            tint,
+        }
     }
 }
+
+/// Generate a triangle list of the tank, later triangles are drawn over earlier ones.
+fn tank_vertices() -> Vec<TankVertex> {
+    let mut vertices = Vec::new();
+    // Treads on both sides, the body between them.
+    rect(&mut vertices, [-A, -B], [-A + 0.07, B], 0.0);
+    rect(&mut vertices, [A - 0.07, -B], [A, B], 0.0);
+    rect(
+        &mut vertices,
+        [-A + 0.06, -B + 0.04],
+        [A - 0.06, B - 0.04],
+        1.0,
+    );
+    // The barrel under the turret, showing where the tank fires.
+    rect(&mut vertices, [-0.03, 0.0], [0.03, BARREL], 0.0);
+    let point = |i: usize| {
+        let (sin, cos) = (2.0 * f32::PI() * i as f32 / TURRET_SEGMENTS as f32).sin_cos();
+        TankVertex::new(cos * TURRET_RADIUS, sin * TURRET_RADIUS - 0.02, TURRET_TINT)
+    };
+    vertices.extend((0..TURRET_SEGMENTS).flat_map(|i| {
+        vec![
+            TankVertex::new(0.0, -0.02, TURRET_TINT),
+            point(i),
+            point(i + 1),
+        ]
+    }));
+    vertices
+}
+
+/// Two triangles of a rectangle between the corners.
+fn rect(vertices: &mut Vec<TankVertex>, min: [f32; 2], max: [f32; 2], tint: f32) {
+    let corners = [
+        TankVertex::new(min[0], min[1], tint),
+        TankVertex::new(max[0], min[1], tint),
+        TankVertex::new(max[0], max[1], tint),
+        TankVertex::new(min[0], max[1], tint),
+    ];
+    vertices.extend([0, 1, 2, 0, 2, 3].iter().map(|&i| corners[i]));
+}
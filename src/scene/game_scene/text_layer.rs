@@ -0,0 +1,566 @@
+//! Text drawn as instanced quads from a glyph atlas.
+//!
+//! Glyphs are rasterized into the atlas the first time they are shown, so any character
+//! of the fonts can be drawn. The bundled font covers Latin, Greek and Cyrillic, Chinese
+//! and other characters it lacks are looked up in the fonts of the system.
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    process::Command,
+};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled, warn};
+use rusttype::{point, Font, GlyphId, Scale};
+use wgpu::util::DeviceExt;
+
+use crate::scene::render_layer::{BasicLayer, Layer, VertexAndInstances};
+
+use super::Vertex;
+
+const BUNDLED_FONT: &[u8] = include_bytes!("../../../assets/fonts/DejaVuSans.ttf");
+/// Chinese fonts coming with Windows and macOS, which usually have no fontconfig.
+const SYSTEM_FONTS: [&str; 4] = [
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simsun.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+];
+
+/// Glyphs are rasterized at this size in pixels, and scaled to the size of the text.
+const RASTER_SIZE: f32 = 48.0;
+const ATLAS_SIZE: u32 = 1024;
+/// Empty pixels around glyphs in the atlas, so neighbours don't bleed in when sampled.
+const PADDING: u32 = 1;
+/// Distance between baselines of two lines, relative to the size of the text.
+const LINE_SPACING: f32 = 1.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// Some lines of text, in the coordinates of the camera.
+#[derive(Clone, Debug)]
+pub struct Text {
+    pub content: String,
+    /// The start of the first baseline, or its center or end as `align` says.
+    pub position: [f32; 2],
+    /// Size of the font, about the height of a line.
+    pub size: f32,
+    pub colour: [f32; 4],
+    pub align: Align,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    /// The bottom left corner.
+    position: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    colour: [f32; 4],
+}
+
+/// Where a glyph is in the atlas, in pixels of [`RASTER_SIZE`].
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    origin: [u32; 2],
+    size: [u32; 2],
+    /// From the pen position on the baseline to the top left corner, y is down.
+    offset: [i32; 2],
+}
+
+/// The atlas has no room for more glyphs.
+struct AtlasFull;
+
+struct Fonts {
+    fonts: Vec<Font<'static>>,
+    /// Files tried for missing characters, whether they loaded or not.
+    tried: HashSet<PathBuf>,
+    /// Characters no font has, they aren't looked up again.
+    missing: HashSet<char>,
+}
+
+/// Glyphs are packed in rows from the top of the texture, it's cleared when it's full.
+struct Atlas {
+    texture: wgpu::Texture,
+    /// None for glyphs without pixels, like spaces.
+    glyphs: HashMap<(usize, GlyphId), Option<AtlasGlyph>>,
+    cursor: [u32; 2],
+    row_height: u32,
+}
+
+pub struct TextLayer {
+    layer: BasicLayer<VertexAndInstances>,
+    bind_group: wgpu::BindGroup,
+    atlas: Atlas,
+    fonts: Fonts,
+}
+
+impl<'a> Layer<'a> for TextLayer {
+    fn sub_render_pass<'b>(&'a self, render_pass: &'b mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        self.layer.sub_render_pass(render_pass);
+    }
+}
+
+impl TextLayer {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: wgpu::Extent3d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph Atlas Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let atlas_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("atlas_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler {
+                            filtering: true,
+                            comparison: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("atlas_bind_group"),
+            layout: &atlas_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline = Self::pipeline(
+            device,
+            format,
+            uniform_bind_group_layout,
+            &atlas_bind_group_layout,
+        );
+
+        // A unit square, scaled to every glyph.
+        let vertices = [
+            Vertex::new(0.0, 0.0),
+            Vertex::new(1.0, 0.0),
+            Vertex::new(1.0, 1.0),
+            Vertex::new(0.0, 0.0),
+            Vertex::new(1.0, 1.0),
+            Vertex::new(0.0, 1.0),
+        ];
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Glyph Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GlyphInstance Buffer"),
+            contents: &[],
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+        });
+
+        TextLayer {
+            layer: BasicLayer {
+                pipeline,
+                buffer: VertexAndInstances {
+                    vertex: vertex_buffer,
+                    vertex_num: vertices.len(),
+                    instance: instance_buffer,
+                    instance_num: 0,
+                    instance_capacity: 0,
+                },
+            },
+            bind_group,
+            atlas: Atlas {
+                texture,
+                glyphs: HashMap::new(),
+                cursor: [0, 0],
+                row_height: 0,
+            },
+            fonts: Fonts::new(),
+        }
+    }
+
+    fn pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        atlas_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::RenderPipeline {
+        let vs_module = device.create_shader_module(&wgpu::include_spirv!("shaders/text.vert.spv"));
+        let fs_module = device.create_shader_module(&wgpu::include_spirv!("shaders/text.frag.spv"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Text Layer Pipeline Layout"),
+                bind_group_layouts: &[uniform_bind_group_layout, atlas_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // Edges of glyphs are partly covered.
+        let blend = wgpu::BlendState {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Layer Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs_module,
+                entry_point: "main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::InputStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float2],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+                        step_mode: wgpu::InputStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            1 => Float2,
+                            2 => Float2,
+                            3 => Float2,
+                            4 => Float2,
+                            5 => Float4
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs_module,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    alpha_blend: blend.clone(),
+                    color_blend: blend,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        })
+    }
+
+    /// Replace the texts drawn, glyphs not in the atlas yet are rasterized.
+    pub fn update_texts(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texts: &[Text]) {
+        let instances = match self.layout(queue, texts) {
+            Ok(instances) => instances,
+            Err(AtlasFull) => {
+                // Forget glyphs which are not shown any more, and try again.
+                self.atlas.clear();
+                self.layout(queue, texts).unwrap_or_else(|AtlasFull| {
+                    warn!("Too many different characters to draw at once");
+                    Vec::new()
+                })
+            }
+        };
+
+        let buffer = &mut self.layer.buffer;
+        if buffer.instance_capacity < instances.len() {
+            // Recreate buffer
+            buffer.instance = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("GlyphInstance Buffer"),
+                contents: bytemuck::cast_slice(&instances),
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            });
+            buffer.instance_capacity = instances.len();
+        } else if !instances.is_empty() {
+            // Just send to the existing buffer
+            queue.write_buffer(&buffer.instance, 0, bytemuck::cast_slice(&instances));
+        }
+        buffer.instance_num = instances.len();
+    }
+
+    fn layout(
+        &mut self,
+        queue: &wgpu::Queue,
+        texts: &[Text],
+    ) -> Result<Vec<GlyphInstance>, AtlasFull> {
+        let scale = Scale::uniform(RASTER_SIZE);
+        let mut instances = Vec::new();
+        for text in texts {
+            // Size in the camera of a pixel in the atlas.
+            let unit = text.size / RASTER_SIZE;
+            for (line_index, line) in text.content.lines().enumerate() {
+                let mut pen = 0.0;
+                let mut last: Option<(usize, GlyphId)> = None;
+                let mut glyphs = Vec::new();
+                for c in line.chars() {
+                    let index = self.fonts.find(c);
+                    let font = &self.fonts.fonts[index];
+                    let id = font.glyph(c).id();
+                    if let Some((last_index, last_id)) = last {
+                        if last_index == index {
+                            pen += font.pair_kerning(scale, last_id, id);
+                        }
+                    }
+                    glyphs.push((index, id, pen));
+                    pen += font.glyph(id).scaled(scale).h_metrics().advance_width;
+                    last = Some((index, id));
+                }
+
+                let start = match text.align {
+                    Align::Left => 0.0,
+                    Align::Center => -pen / 2.0,
+                    Align::Right => -pen,
+                };
+                let x = text.position[0] + start * unit;
+                let y = text.position[1] - line_index as f32 * text.size * LINE_SPACING;
+                for (index, id, pen) in glyphs {
+                    let glyph = match self.atlas.glyph(queue, &self.fonts, index, id)? {
+                        Some(glyph) => glyph,
+                        None => continue,
+                    };
+                    let (width, height) = (glyph.size[0] as f32, glyph.size[1] as f32);
+                    let uv = |x: u32, y: u32| {
+                        [x as f32 / ATLAS_SIZE as f32, y as f32 / ATLAS_SIZE as f32]
+                    };
+                    instances.push(GlyphInstance {
+                        position: [
+                            x + (pen + glyph.offset[0] as f32) * unit,
+                            y - (glyph.offset[1] as f32 + height) * unit,
+                        ],
+                        size: [width * unit, height * unit],
+                        uv_min: uv(glyph.origin[0], glyph.origin[1]),
+                        uv_max: uv(
+                            glyph.origin[0] + glyph.size[0],
+                            glyph.origin[1] + glyph.size[1],
+                        ),
+                        colour: text.colour,
+                    });
+                }
+            }
+        }
+        Ok(instances)
+    }
+}
+
+impl Fonts {
+    fn new() -> Fonts {
+        let bundled = Font::try_from_bytes(BUNDLED_FONT).expect("The bundled font is invalid");
+        Fonts {
+            fonts: vec![bundled],
+            tried: HashSet::new(),
+            missing: HashSet::new(),
+        }
+    }
+
+    /// Index of the first font having the character, a font of the system having it is loaded
+    /// if none does. The bundled font draws a box if no font has it.
+    fn find(&mut self, c: char) -> usize {
+        if let Some(index) = self.position(c) {
+            return index;
+        }
+        if self.missing.contains(&c) {
+            return 0;
+        }
+        let candidates = fontconfig_files(c)
+            .into_iter()
+            .chain(SYSTEM_FONTS.iter().map(PathBuf::from));
+        for path in candidates {
+            if !self.tried.insert(path.clone()) {
+                continue;
+            }
+            let font = match fs::read(&path).ok().and_then(Font::try_from_vec) {
+                Some(font) => font,
+                None => continue,
+            };
+            info!("Loaded font {} for characters missing in the bundled one", path.display());
+            let found = font.glyph(c).id() != GlyphId(0);
+            self.fonts.push(font);
+            if found {
+                return self.fonts.len() - 1;
+            }
+        }
+        warn!("No font has {:?}, install a font covering it to draw it", c);
+        self.missing.insert(c);
+        0
+    }
+
+    fn position(&self, c: char) -> Option<usize> {
+        self.fonts
+            .iter()
+            .position(|font| font.glyph(c).id() != GlyphId(0))
+    }
+}
+
+/// Files of the fonts having the character, as fontconfig lists them.
+/// Empty if fontconfig isn't installed.
+fn fontconfig_files(c: char) -> Vec<PathBuf> {
+    let output = Command::new("fc-list")
+        .arg(format!(":charset={:x}", c as u32))
+        .arg("--format=%{file}\n")
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let mut files: Vec<PathBuf> = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(PathBuf::from)
+                .collect();
+            // fontconfig lists them in no particular order.
+            files.sort();
+            files
+        }
+        _ => Vec::new(),
+    }
+}
+
+impl Atlas {
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor = [0, 0];
+        self.row_height = 0;
+    }
+
+    /// Where the glyph is in the atlas, it's rasterized if it isn't there.
+    fn glyph(
+        &mut self,
+        queue: &wgpu::Queue,
+        fonts: &Fonts,
+        font: usize,
+        id: GlyphId,
+    ) -> Result<Option<AtlasGlyph>, AtlasFull> {
+        if let Some(&glyph) = self.glyphs.get(&(font, id)) {
+            return Ok(glyph);
+        }
+        let glyph = fonts.fonts[font]
+            .glyph(id)
+            .scaled(Scale::uniform(RASTER_SIZE))
+            .positioned(point(0.0, 0.0));
+        let bounds = match glyph.pixel_bounding_box() {
+            Some(bounds) => bounds,
+            None => {
+                self.glyphs.insert((font, id), None);
+                return Ok(None);
+            }
+        };
+        let size = [bounds.width() as u32, bounds.height() as u32];
+        if self.cursor[0] + size[0] + PADDING > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height + PADDING];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + size[1] + PADDING > ATLAS_SIZE {
+            return Err(AtlasFull);
+        }
+        let origin = self.cursor;
+        self.cursor[0] += size[0] + PADDING;
+        self.row_height = self.row_height.max(size[1]);
+
+        let mut pixels = vec![0u8; (size[0] * size[1]) as usize];
+        glyph.draw(|x, y, coverage| {
+            pixels[(y * size[0] + x) as usize] = (coverage * 255.0).round() as u8;
+        });
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+            },
+            &pixels,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: size[0],
+                rows_per_image: size[1],
+            },
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth: 1,
+            },
+        );
+
+        let glyph = AtlasGlyph {
+            origin,
+            size,
+            offset: [bounds.min.x, bounds.min.y],
+        };
+        self.glyphs.insert((font, id), Some(glyph));
+        Ok(Some(glyph))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_font_draws_latin() {
+        let mut fonts = Fonts::new();
+        for c in "OpenTanktrouble 0123456789".chars() {
+            assert_eq!(fonts.find(c), 0, "{:?}", c);
+            assert_ne!(fonts.fonts[0].glyph(c).id(), GlyphId(0), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn system_font_draws_chinese() {
+        let mut fonts = Fonts::new();
+        let index = fonts.find('中');
+        if index == 0 {
+            println!("Skipped, no font of the system has 中");
+            return;
+        }
+        let glyph = fonts.fonts[index]
+            .glyph('中')
+            .scaled(Scale::uniform(RASTER_SIZE))
+            .positioned(point(0.0, 0.0));
+        let mut covered = 0;
+        glyph.draw(|_, _, coverage| covered += (coverage > 0.5) as usize);
+        assert!(covered > 0);
+        // Found in the loaded fonts the next time.
+        let tried = fonts.tried.len();
+        assert_eq!(fonts.find('中'), index);
+        assert_eq!(fonts.tried.len(), tried);
+    }
+}
@@ -0,0 +1,402 @@
+//! The lobby of a LAN match: local players join, pick colours and get ready,
+//! then the host starts the match.
+use std::{error::Error, net::SocketAddr, time};
+
+use crossbeam_channel::{Receiver, Sender, tick};
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+use winit::event::{ElementState, VirtualKeyCode};
+
+use crate::input::{
+    Controller,
+    input_center::{EventKind, InputCenter, Source},
+    PlayerInput,
+};
+use crate::net::{
+    client::Client,
+    discovery::{Browser, HostedGame, DEFAULT_PORT},
+    protocol::{LobbyState, Seat, ServerMessage},
+};
+
+use super::{
+    client_scene,
+    game_scene::{
+        text_layer::{Align, Text},
+        PHYSICAL_DT,
+    },
+    palette::{self, COLOURS},
+    slots::{self, SlotsFrame, SlotsRender, ALERT, BACKGROUND, DARK, LIGHT, READY},
+    SceneRender, SceneUpdater,
+};
+
+/// Ticks between two seat messages if nothing changes.
+const SEATS_INTERVAL: u32 = 10;
+/// Games listed at once, the list scrolls to keep the selected one shown.
+const LIST_ROWS: usize = 7;
+
+pub struct LobbySceneUpdater {
+    /// The server to join, games on the LAN are listed to choose from if it's None.
+    address: Option<SocketAddr>,
+    /// Why the player came back to the lobby, e.g. the server of the last match stopped answering.
+    notice: Option<String>,
+
+    frame_update_sender: Sender<SlotsFrame>,
+}
+
+enum Action {
+    Join(Source),
+    Start,
+}
+
+/// The connection, the local players and colours of all players when the match starts.
+type Started = (Client, Vec<Box<dyn Controller>>, Vec<usize>);
+
+struct LocalPlayer {
+    controller: Box<dyn Controller>,
+    source: Source,
+    seat: Seat,
+    /// Changes of the input pick colours and toggle ready.
+    last_input: PlayerInput,
+}
+
+pub fn new(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    address: Option<SocketAddr>,
+    notice: Option<String>,
+) -> (SlotsRender, LobbySceneUpdater) {
+    let (render, frame_update_sender) = slots::render(device, format);
+    (
+        render,
+        LobbySceneUpdater {
+            address,
+            notice,
+            frame_update_sender,
+        },
+    )
+}
+
+impl LobbySceneUpdater {
+    fn manage(&self, input_center: &InputCenter) -> Result<Started, Box<dyn Error>> {
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        if let Some(notice) = &self.notice {
+            info!("{}", notice);
+        }
+        let address = match self.address {
+            Some(address) => address,
+            None => self.browse(input_center, &ticker)?,
+        };
+        let mut client = Client::connect(address)?;
+        info!("Entered the lobby on {}, press a fire key or the south button to join", address);
+        let fire_keys: Vec<String> = input_center
+            .key_bindings()
+            .clusters
+            .iter()
+            .map(|cluster| cluster.fire.to_string())
+            .collect();
+        let instructions = format!(
+            "Press {} or the south button to join, 1 2 3 add bots\n\
+             Turn to pick a colour, fire to get ready",
+            fire_keys.join(" "),
+        );
+        self.frame_update_sender
+            .send(draw_lobby(None, 0, &instructions))?;
+        let mut players: Vec<LocalPlayer> = Vec::new();
+        let mut lobby: Option<LobbyState> = None;
+        let mut ticks = 0u32;
+
+        loop {
+            ticker.recv()?;
+            let mut changed = false;
+            for event in input_center.update().events {
+                match action(input_center, &event.kind) {
+                    Some(Action::Join(source)) => {
+                        changed |= join(input_center, &mut players, source);
+                    }
+                    Some(Action::Start) => match &lobby {
+                        Some(lobby) if lobby.host => client.start()?,
+                        _ => info!("Only the host can start the match"),
+                    },
+                    None => {}
+                }
+            }
+            changed |= pick(&mut players, lobby.as_ref());
+
+            for message in client.receive()? {
+                match message {
+                    ServerMessage::Lobby(state) => {
+                        if lobby.as_ref() != Some(&state) {
+                            log_lobby(&state, players.len());
+                            let frame = draw_lobby(Some(&state), players.len(), &instructions);
+                            self.frame_update_sender.send(frame)?;
+                            lobby = Some(state);
+                        }
+                    }
+                    // The first maze or snapshot of the match.
+                    ServerMessage::Maze { .. } | ServerMessage::Snapshot(_) => {
+                        info!("The match starts, joined as player {}", client.first_player());
+                        let controllers = players.into_iter().map(|p| p.controller).collect();
+                        let colours = lobby.map_or_else(Vec::new, |lobby| {
+                            lobby.seats.iter().map(|seat| seat.colour).collect()
+                        });
+                        return Ok((client, controllers, colours));
+                    }
+                    ServerMessage::Refused(reason) => return Err(reason.into()),
+                    ServerMessage::Game(_) | ServerMessage::Welcome => {}
+                }
+            }
+
+            ticks += 1;
+            if changed || ticks % SEATS_INTERVAL == 0 {
+                client.send_seats(players.iter().map(|p| p.seat).collect())?;
+            }
+        }
+    }
+
+    /// List the games on the LAN, Up and Down select one, Enter joins it.
+    fn browse(
+        &self,
+        input_center: &InputCenter,
+        ticker: &Receiver<time::Instant>,
+    ) -> Result<SocketAddr, Box<dyn Error>> {
+        let mut browser = Browser::new(DEFAULT_PORT)?;
+        let mut selected = 0;
+        info!("Looking for games on the LAN");
+        let notice = self.notice.as_deref();
+        self.frame_update_sender
+            .send(draw_games(browser.games(), selected, notice))?;
+        loop {
+            ticker.recv()?;
+            let mut changed = browser.update()?;
+            let games = browser.games();
+            for key in input_center.update().pressed_keys() {
+                match key {
+                    VirtualKeyCode::Up if selected > 0 => selected -= 1,
+                    VirtualKeyCode::Down if selected + 1 < games.len() => selected += 1,
+                    VirtualKeyCode::Return if selected < games.len() => {
+                        return Ok(games[selected].address);
+                    }
+                    _ => continue,
+                }
+                changed = true;
+            }
+            selected = selected.min(games.len().saturating_sub(1));
+            if changed {
+                info!("{} games found, Up and Down select one, Enter joins it", games.len());
+                for (i, game) in games.iter().enumerate() {
+                    let info = &game.info;
+                    info!(
+                        "{} {} with {}/{} players{}",
+                        if i == selected { ">" } else { " " },
+                        game.address,
+                        info.players,
+                        info.places,
+                        if info.started { ", started" } else { "" },
+                    );
+                }
+                self.frame_update_sender
+                    .send(draw_games(games, selected, notice))?;
+            }
+        }
+    }
+}
+
+/// Returns false if the controller has joined already.
+fn join(input_center: &InputCenter, players: &mut Vec<LocalPlayer>, source: Source) -> bool {
+    let bot = matches!(source, Source::Bot(_));
+    if !bot && players.iter().any(|player| player.source == source) {
+        return false;
+    }
+    let controller = input_center.create_controller(source);
+    let taken: Vec<usize> = players.iter().map(|player| player.seat.colour).collect();
+    let last_input = controller.input();
+    players.push(LocalPlayer {
+        controller,
+        source,
+        // The server gives another colour if this one is taken by remote players.
+        seat: Seat {
+            colour: palette::free_colour(&taken, 0),
+            ready: bot,
+        },
+        last_input,
+    });
+    true
+}
+
+/// Turning left or right picks the previous or the next colour, firing toggles ready.
+/// Returns true if any seat changes.
+fn pick(players: &mut [LocalPlayer], lobby: Option<&LobbyState>) -> bool {
+    let seats: &[Seat] = lobby.map_or(&[], |lobby| &lobby.seats);
+    let first = lobby.map_or(0, |lobby| lobby.first);
+    let mut changed = false;
+    for (i, player) in players.iter_mut().enumerate() {
+        if let Source::Bot(_) = player.source {
+            continue;
+        }
+        let input = player.controller.input();
+        let (turn, last_turn) = (input.movement.0, player.last_input.movement.0);
+        if turn.abs() > 0.5 && last_turn.abs() <= 0.5 {
+            // Colours of the others, as the server says.
+            let taken: Vec<usize> = seats
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != first + i)
+                .map(|(_, seat)| seat.colour)
+                .collect();
+            let current = seats.get(first + i).map_or(player.seat.colour, |s| s.colour);
+            player.seat.colour = palette::next_colour(&taken, current, turn > 0.0);
+            changed = true;
+        }
+        if input.fire && !player.last_input.fire {
+            player.seat.ready = !player.seat.ready;
+            changed = true;
+        }
+        player.last_input = input;
+    }
+    changed
+}
+
+fn log_lobby(lobby: &LobbyState, local: usize) {
+    let players: Vec<String> = lobby
+        .seats
+        .iter()
+        .enumerate()
+        .map(|(i, seat)| {
+            let mine = (lobby.first..lobby.first + local).contains(&i);
+            format!(
+                "{}{}{}",
+                COLOURS[seat.colour].name,
+                if mine { " (you)" } else { "" },
+                if seat.ready { " ready" } else { "" },
+            )
+        })
+        .collect();
+    info!("Lobby: {}", players.join(", "));
+    if lobby.host && lobby.seats.len() >= 2 && lobby.seats.iter().all(|seat| seat.ready) {
+        info!("Everyone is ready, press Enter to start");
+    }
+}
+
+/// The games found, the selected one is marked, and `notice` above them.
+fn draw_games(games: &[HostedGame], selected: usize, notice: Option<&str>) -> SlotsFrame {
+    let mut frame = SlotsFrame::default();
+    frame.text("Games on the LAN", [0.0, 2.0], 0.3, DARK);
+    if let Some(notice) = notice {
+        frame.text(notice, [0.0, 1.55], 0.16, ALERT);
+    }
+
+    let first = (selected + 1).saturating_sub(LIST_ROWS);
+    for (row, (i, game)) in games.iter().enumerate().skip(first).take(LIST_ROWS).enumerate() {
+        let y = 1.1 - row as f32 * 0.5;
+        let background = if i == selected { LIGHT } else { BACKGROUND };
+        frame.shapes.rect([0.0, y], [6.0, 0.4], background);
+        if i == selected {
+            frame.shapes.rect([-2.9, y], [0.2, 0.4], DARK);
+        }
+        let info = &game.info;
+        frame.texts.push(Text {
+            content: game.address.to_string(),
+            position: [-2.6, y - 0.06],
+            size: 0.18,
+            colour: DARK,
+            align: Align::Left,
+        });
+        frame.texts.push(Text {
+            content: format!(
+                "{}/{} players{}",
+                info.players,
+                info.places,
+                if info.started { ", started" } else { "" },
+            ),
+            position: [2.8, y - 0.06],
+            size: 0.18,
+            colour: DARK,
+            align: Align::Right,
+        });
+    }
+
+    let message = if games.is_empty() {
+        "Looking for games on the LAN"
+    } else {
+        "Up and Down select a game, Enter joins it"
+    };
+    frame.text(message, [0.0, -2.3], 0.2, DARK);
+    frame
+}
+
+/// A slot for every seat in the lobby, `local` seats from [`LobbyState::first`] are ours.
+fn draw_lobby(lobby: Option<&LobbyState>, local: usize, instructions: &str) -> SlotsFrame {
+    let mut frame = SlotsFrame::default();
+    let seats: &[Seat] = lobby.map_or(&[], |lobby| &lobby.seats);
+    let first = lobby.map_or(0, |lobby| lobby.first);
+    for (i, seat) in seats.iter().enumerate() {
+        let center = slots::slot_center(i, seats.len());
+        let mine = (first..first + local).contains(&i);
+        draw_seat(&mut frame, center, seat, mine);
+    }
+
+    let message = match lobby {
+        None => "Waiting for the server",
+        Some(lobby) if seats.len() >= 2 && seats.iter().all(|seat| seat.ready) => {
+            if lobby.host {
+                "Everyone is ready, press Enter to start"
+            } else {
+                "Everyone is ready, waiting for the host to start"
+            }
+        }
+        Some(_) => instructions,
+    };
+    frame.message(message);
+    frame
+}
+
+/// The tank in the colour of the seat, whether it's ours, and the ready state at the bottom.
+fn draw_seat(frame: &mut SlotsFrame, center: [f32; 2], seat: &Seat, mine: bool) {
+    let colour = COLOURS[seat.colour].rgba();
+    let [x, y] = center;
+    frame.slot(center, colour);
+    frame.tank([x, y + 0.3], colour);
+    frame.text(if mine { "you" } else { "remote" }, [x, y - 0.4], 0.16, DARK);
+    if seat.ready {
+        frame.status(center, "ready", READY);
+    } else {
+        frame.status(center, "not ready", LIGHT);
+    }
+}
+
+/// Controllers join as in [`InputCenter::joining`], Enter starts the match.
+fn action(input_center: &InputCenter, event: &EventKind) -> Option<Action> {
+    if let Some(source) = input_center.joining(event) {
+        return Some(Action::Join(source));
+    }
+    match event {
+        EventKind::Keyboard(input)
+            if input.state == ElementState::Pressed
+                && input.virtual_keycode == Some(VirtualKeyCode::Return) =>
+        {
+            Some(Action::Start)
+        }
+        _ => None,
+    }
+}
+
+impl SceneUpdater for LobbySceneUpdater {
+    fn update(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        input_center: &InputCenter,
+    ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
+        match self.manage(input_center) {
+            Ok((client, controllers, colours)) => {
+                let (render, updater) =
+                    client_scene::new(device, format, client, controllers, colours);
+                Some((Box::new(render), Box::new(updater)))
+            }
+            Err(err) => {
+                error!("{}", err);
+                None
+            }
+        }
+    }
+}
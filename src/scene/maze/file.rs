@@ -0,0 +1,337 @@
+//! Save and load mazes, so handcrafted arenas can be played.
+//!
+//! Two formats are supported, chosen by the extension of the file:
+//!
+//! - `.ron`: a structured format, each row of walls is a string of `1` (wall) and `0` (no wall),
+//!   rows are ordered from the bottom of the maze to the top.
+//! - Anything else: an ASCII-art grid as it looks in the game, `+` at every corner,
+//!   `-` for horizontal walls and `|` for vertical walls.
+//!
+//! ```text
+//! +-+-+-+
+//! |     |
+//! + +-+ +
+//! | |   |
+//! +-+-+-+
+//! ```
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::Maze;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Ron(ron::Error),
+}
+
+/// A bad wall spec in the ASCII-art grid, `line` and `column` start from 1.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// The structured form of a maze, see the module documentation.
+#[derive(Serialize, Deserialize)]
+struct MazeFile {
+    width: usize,
+    height: usize,
+    horizontal_walls: Vec<String>,
+    vertical_walls: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Result<Maze, LoadError> {
+    let text = fs::read_to_string(path)?;
+    if is_ron(path) {
+        Maze::from_ron(&text)
+    } else {
+        Ok(Maze::from_ascii(&text)?)
+    }
+}
+
+pub fn save(maze: &Maze, path: &Path) -> io::Result<()> {
+    let text = if is_ron(path) {
+        maze.to_ron()
+    } else {
+        maze.to_ascii()
+    };
+    fs::write(path, text)
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "ron")
+}
+
+impl Maze {
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::new();
+        for y in (0..=self.height).rev() {
+            for x in 0..self.width {
+                text.push('+');
+                text.push(if self.horizontal_walls[y][x] { '-' } else { ' ' });
+            }
+            text.push_str("+\n");
+            if y == 0 {
+                break;
+            }
+            for x in 0..=self.width {
+                text.push(if self.vertical_walls[y - 1][x] { '|' } else { ' ' });
+                if x < self.width {
+                    text.push(' ');
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn from_ascii(text: &str) -> Result<Maze, ParseError> {
+        let lines: Vec<&str> = text.trim_end().lines().map(|l| l.trim_end()).collect();
+        let first_len = lines.first().map_or(0, |l| l.chars().count());
+        if first_len < 3 || first_len % 2 == 0 || lines.len() < 3 || lines.len() % 2 == 0 {
+            return Err(ParseError::new(
+                1,
+                1,
+                "the grid should have an odd number (at least 3) of rows and columns",
+            ));
+        }
+        let width = (first_len - 1) / 2;
+        let height = (lines.len() - 1) / 2;
+        let mut maze = Maze::closed(width, height);
+
+        for (i, line) in lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.len() != first_len {
+                return Err(ParseError::new(
+                    i + 1,
+                    chars.len().min(first_len) + 1,
+                    format!("expect {} columns, found {}", first_len, chars.len()),
+                ));
+            }
+            let on_border_row = i == 0 || i == lines.len() - 1;
+            for (j, &c) in chars.iter().enumerate() {
+                let on_border = on_border_row || j == 0 || j == first_len - 1;
+                let error = |message: String| Err(ParseError::new(i + 1, j + 1, message));
+                let wall = match (i % 2, j % 2, c) {
+                    // Corners
+                    (0, 0, '+') => continue,
+                    (0, 0, _) => return error(format!("expect '+' at a corner, found {:?}", c)),
+                    // Horizontal walls
+                    (0, 1, '-') => true,
+                    (0, 1, ' ') if !on_border => false,
+                    (0, 1, _) if on_border => {
+                        return error(format!("expect '-' on the border, found {:?}", c))
+                    }
+                    (0, 1, _) => return error(format!("expect '-' or ' ', found {:?}", c)),
+                    // Vertical walls
+                    (1, 0, '|') => true,
+                    (1, 0, ' ') if !on_border => false,
+                    (1, 0, _) if on_border => {
+                        return error(format!("expect '|' on the border, found {:?}", c))
+                    }
+                    (1, 0, _) => return error(format!("expect '|' or ' ', found {:?}", c)),
+                    // Inside of cells
+                    (_, _, ' ') => continue,
+                    (_, _, _) => return error(format!("expect ' ' inside a cell, found {:?}", c)),
+                };
+                let y = height - i / 2;
+                let x = j / 2;
+                if i % 2 == 0 {
+                    maze.horizontal_walls[y][x] = wall;
+                } else {
+                    maze.vertical_walls[y - 1][x] = wall;
+                }
+            }
+        }
+        Ok(maze)
+    }
+
+    pub fn to_ron(&self) -> String {
+        let walls_to_strings = |walls: &Vec<Vec<bool>>| {
+            walls
+                .iter()
+                .map(|row| row.iter().map(|&w| if w { '1' } else { '0' }).collect())
+                .collect()
+        };
+        let file = MazeFile {
+            width: self.width,
+            height: self.height,
+            horizontal_walls: walls_to_strings(&self.horizontal_walls),
+            vertical_walls: walls_to_strings(&self.vertical_walls),
+        };
+        // This never fails because MazeFile only contains numbers and strings.
+        ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::new()).unwrap()
+    }
+
+    pub fn from_ron(text: &str) -> Result<Maze, LoadError> {
+        let file: MazeFile = ron::de::from_str(text)?;
+        let strings_to_walls = |name, rows: &Vec<String>, row_num, row_len| {
+            if rows.len() != row_num {
+                return invalid(format!("{} should have {} rows", name, row_num));
+            }
+            let mut walls = Vec::with_capacity(row_num);
+            for (y, row) in rows.iter().enumerate() {
+                let row: Vec<bool> = row
+                    .chars()
+                    .map(|c| match c {
+                        '0' => Ok(false),
+                        '1' => Ok(true),
+                        _ => Err(c),
+                    })
+                    .collect::<Result<_, _>>()
+                    .or_else(|c| invalid(format!("{}[{}]: invalid wall {:?}", name, y, c)))?;
+                if row.len() != row_len {
+                    return invalid(format!("{}[{}] should have {} walls", name, y, row_len));
+                }
+                walls.push(row);
+            }
+            Ok(walls)
+        };
+        let (width, height) = (file.width, file.height);
+        if width == 0 || height == 0 {
+            return invalid("width and height should be positive".to_string());
+        }
+        let maze = Maze {
+            width,
+            height,
+            horizontal_walls: strings_to_walls(
+                "horizontal_walls",
+                &file.horizontal_walls,
+                height + 1,
+                width,
+            )?,
+            vertical_walls: strings_to_walls(
+                "vertical_walls",
+                &file.vertical_walls,
+                height,
+                width + 1,
+            )?,
+        };
+        let closed_border = maze.horizontal_walls[0].iter().all(|&w| w)
+            && maze.horizontal_walls[height].iter().all(|&w| w)
+            && maze.vertical_walls.iter().all(|row| row[0] && row[width]);
+        if !closed_border {
+            return invalid("walls on the border can't be removed".to_string());
+        }
+        Ok(maze)
+    }
+}
+
+/// An error of the structured format which is not about the syntax.
+fn invalid<T>(message: String) -> Result<T, LoadError> {
+    Err(LoadError::Parse(ParseError::new(0, 0, message)))
+}
+
+impl ParseError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Parse(err) => write!(f, "{}", err),
+            LoadError::Ron(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> Self {
+        LoadError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for LoadError {
+    fn from(err: ron::Error) -> Self {
+        LoadError::Ron(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::scene::maze::ARENA;
+
+    use super::*;
+
+    #[test]
+    fn ascii_round_trip() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        assert_eq!((maze.width, maze.height), (3, 2));
+        assert!(!maze.horizontal_walls[1][0]);
+        assert!(maze.horizontal_walls[1][1]);
+        assert!(maze.vertical_walls[0][1]);
+        assert!(!maze.vertical_walls[1][1]);
+        assert_eq!(maze.to_ascii(), ARENA);
+
+        for seed in 0..10 {
+            let maze = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(seed));
+            let text = maze.to_ascii();
+            assert_eq!(Maze::from_ascii(&text).unwrap().to_ascii(), text);
+        }
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        for seed in 0..10 {
+            let maze = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(seed));
+            let text = maze.to_ron();
+            assert_eq!(Maze::from_ron(&text).unwrap().to_ascii(), maze.to_ascii());
+        }
+    }
+
+    #[test]
+    fn ascii_errors() {
+        let error = |text: &str| Maze::from_ascii(text).unwrap_err();
+        let bad_wall = ARENA.replacen("+ +-+ +", "+ +x+ +", 1);
+        assert_eq!((error(&bad_wall).line, error(&bad_wall).column), (3, 4));
+        let bad_border = ARENA.replacen("| |   |", "  |   |", 1);
+        assert_eq!((error(&bad_border).line, error(&bad_border).column), (4, 1));
+        let bad_corner = ARENA.replacen("+ +-+ +", "+ +-- +", 1);
+        assert_eq!((error(&bad_corner).line, error(&bad_corner).column), (3, 5));
+        let short_line = ARENA.replacen("|     |", "|    |", 1);
+        assert_eq!(error(&short_line).line, 2);
+        assert_eq!(error("+-+\n").line, 1);
+    }
+
+    #[test]
+    fn ron_errors() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        assert!(Maze::from_ron(&maze.to_ron().replacen("111", "101", 1)).is_err());
+        assert!(Maze::from_ron(&maze.to_ron().replacen("111", "1x1", 1)).is_err());
+        assert!(Maze::from_ron("(width: 3)").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+use super::{Cell, Direction, Maze};
+
+/// A maze generator carves passages into a maze whose walls are all closed.
+/// The result must be a perfect maze, which means there is exactly one path
+/// between every two cells.
+pub trait Generator {
+    fn carve(&self, maze: &mut Maze, rng: &mut dyn RngCore);
+}
+
+/// Depth-first search, which makes long and twisty corridors.
+pub struct RecursiveBacktracker;
+
+/// Randomized Kruskal's algorithm, which makes lots of short dead ends.
+pub struct Kruskal;
+
+/// Randomized Prim's algorithm, which makes corridors radiate from the start cell.
+pub struct Prim;
+
+/// Wilson's algorithm with loop-erased random walks, which picks a uniform
+/// spanning tree among all possible mazes.
+pub struct Wilson;
+
+impl Generator for RecursiveBacktracker {
+    fn carve(&self, maze: &mut Maze, rng: &mut dyn RngCore) {
+        let mut visited = vec![vec![false; maze.width]; maze.height];
+        let start = random_cell(maze, rng);
+        visited[start.1][start.0] = true;
+        let mut stack = vec![start];
+        while let Some(&cell) = stack.last() {
+            let unvisited: Vec<(Direction, Cell)> = maze.neighbours(cell)
+                .filter(|&(_, (x, y))| !visited[y][x])
+                .collect();
+            match unvisited.choose(rng) {
+                Some(&(direction, next)) => {
+                    maze.set_wall(cell, direction, false);
+                    visited[next.1][next.0] = true;
+                    stack.push(next);
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl Generator for Kruskal {
+    fn carve(&self, maze: &mut Maze, rng: &mut dyn RngCore) {
+        let mut walls: Vec<(Cell, Direction)> = maze.cells()
+            .flat_map(|cell| vec![(cell, Direction::Top), (cell, Direction::Right)])
+            .filter(|&(cell, direction)| maze.neighbour(cell, direction).is_some())
+            .collect();
+        walls.shuffle(rng);
+
+        // Disjoint sets of cells, each cell is indexed by `x + y * width`.
+        let mut parents: Vec<usize> = (0..maze.width * maze.height).collect();
+        fn find(parents: &mut [usize], i: usize) -> usize {
+            let mut root = i;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            // Path compression
+            let mut i = i;
+            while parents[i] != root {
+                let next = parents[i];
+                parents[i] = root;
+                i = next;
+            }
+            root
+        }
+
+        for (cell, direction) in walls {
+            let (x, y) = maze.neighbour(cell, direction).unwrap();
+            let root1 = find(&mut parents, cell.0 + cell.1 * maze.width);
+            let root2 = find(&mut parents, x + y * maze.width);
+            if root1 != root2 {
+                parents[root1] = root2;
+                maze.set_wall(cell, direction, false);
+            }
+        }
+    }
+}
+
+impl Generator for Prim {
+    fn carve(&self, maze: &mut Maze, rng: &mut dyn RngCore) {
+        let mut in_maze = vec![vec![false; maze.width]; maze.height];
+        let start = random_cell(maze, rng);
+        in_maze[start.1][start.0] = true;
+        let mut frontier: Vec<(Cell, Direction)> =
+            maze.neighbours(start).map(|(d, _)| (start, d)).collect();
+
+        while !frontier.is_empty() {
+            let (cell, direction) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            let next = maze.neighbour(cell, direction).unwrap();
+            if in_maze[next.1][next.0] {
+                continue;
+            }
+            maze.set_wall(cell, direction, false);
+            in_maze[next.1][next.0] = true;
+            frontier.extend(
+                maze.neighbours(next)
+                    .filter(|&(_, (x, y))| !in_maze[y][x])
+                    .map(|(d, _)| (next, d)),
+            );
+        }
+    }
+}
+
+impl Generator for Wilson {
+    fn carve(&self, maze: &mut Maze, rng: &mut dyn RngCore) {
+        let mut in_maze = vec![vec![false; maze.width]; maze.height];
+        let start = random_cell(maze, rng);
+        in_maze[start.1][start.0] = true;
+
+        let mut remaining: Vec<Cell> = maze.cells().collect();
+        remaining.shuffle(rng);
+        // The direction each cell left at the last time of the walk.
+        // Overwriting it when the walk visits a cell again erases the loop.
+        let mut walk = vec![vec![Direction::Top; maze.width]; maze.height];
+        for begin in remaining {
+            if in_maze[begin.1][begin.0] {
+                continue;
+            }
+            // Random walk until reaching the maze
+            let mut cell = begin;
+            while !in_maze[cell.1][cell.0] {
+                let (direction, next) = *maze.neighbours(cell)
+                    .collect::<Vec<_>>()
+                    .choose(rng)
+                    .unwrap();
+                walk[cell.1][cell.0] = direction;
+                cell = next;
+            }
+            // Carve the loop-erased path
+            let mut cell = begin;
+            while !in_maze[cell.1][cell.0] {
+                let direction = walk[cell.1][cell.0];
+                maze.set_wall(cell, direction, false);
+                in_maze[cell.1][cell.0] = true;
+                cell = maze.neighbour(cell, direction).unwrap();
+            }
+        }
+    }
+}
+
+/// Remove dead ends with the possibility of `factor` to create loops.
+/// A dead end is opened towards another dead end if possible.
+/// `0.0` keeps the maze perfect and `1.0` removes all dead ends.
+pub fn braid(maze: &mut Maze, factor: f64, rng: &mut dyn RngCore) {
+    let factor = factor.clamp(0.0, 1.0);
+    let is_dead_end = |maze: &Maze, cell| maze.open_neighbours(cell).count() == 1;
+
+    let mut dead_ends: Vec<Cell> = maze.cells().filter(|&c| is_dead_end(maze, c)).collect();
+    dead_ends.shuffle(rng);
+    for cell in dead_ends {
+        // The dead end might be removed by its neighbour already.
+        if !is_dead_end(maze, cell) || !rng.gen_bool(factor) {
+            continue;
+        }
+        let walls: Vec<(Direction, Cell)> = maze.neighbours(cell)
+            .filter(|&(direction, _)| maze.has_wall(cell, direction))
+            .collect();
+        let towards_dead_ends: Vec<(Direction, Cell)> = walls
+            .iter()
+            .copied()
+            .filter(|&(_, next)| is_dead_end(maze, next))
+            .collect();
+        let choice = towards_dead_ends.choose(rng).or_else(|| walls.choose(rng));
+        if let Some(&(direction, _)) = choice {
+            maze.set_wall(cell, direction, false);
+        }
+    }
+}
+
+fn random_cell(maze: &Maze, rng: &mut dyn RngCore) -> Cell {
+    (rng.gen_range(0..maze.width), rng.gen_range(0..maze.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const SIZES: [(usize, usize); 5] = [(1, 1), (1, 7), (2, 2), (8, 5), (12, 10)];
+
+    fn generators() -> Vec<(&'static str, Box<dyn Generator>)> {
+        vec![
+            ("RecursiveBacktracker", Box::new(RecursiveBacktracker)),
+            ("Kruskal", Box::new(Kruskal)),
+            ("Prim", Box::new(Prim)),
+            ("Wilson", Box::new(Wilson)),
+        ]
+    }
+
+    fn passages(maze: &Maze) -> usize {
+        maze.cells()
+            .map(|cell| maze.open_neighbours(cell).count())
+            .sum::<usize>()
+            / 2
+    }
+
+    #[test]
+    fn perfect_mazes_are_connected_trees() {
+        for (name, generator) in generators() {
+            for seed in 0..20 {
+                for &(width, height) in SIZES.iter() {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let maze = Maze::generate(width, height, generator.as_ref(), 0.0, &mut rng);
+                    assert!(maze.is_connected(), "{} seed {}", name, seed);
+                    assert_eq!(passages(&maze), width * height - 1, "{} seed {}", name, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn braided_mazes_are_connected() {
+        for (name, generator) in generators() {
+            for seed in 0..20 {
+                for &(width, height) in SIZES.iter() {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    let maze = Maze::generate(width, height, generator.as_ref(), 0.5, &mut rng);
+                    assert!(maze.is_connected(), "{} seed {}", name, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn full_braid_removes_all_dead_ends() {
+        for (name, generator) in generators() {
+            for seed in 0..20 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let maze = Maze::generate(9, 7, generator.as_ref(), 1.0, &mut rng);
+                assert!(maze.is_connected(), "{} seed {}", name, seed);
+                assert!(
+                    maze.cells().all(|cell| maze.open_neighbours(cell).count() > 1),
+                    "{} seed {}",
+                    name,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn random_mazes_are_connected() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!(Maze::new(2, &mut rng).is_connected());
+        }
+    }
+
+    #[test]
+    fn border_walls_can_not_be_removed() {
+        let mut maze = Maze::closed(3, 3);
+        maze.set_wall((0, 0), Direction::Left, false);
+        maze.set_wall((2, 2), Direction::Top, false);
+        assert!(maze.has_wall((0, 0), Direction::Left));
+        assert!(maze.has_wall((2, 2), Direction::Top));
+        maze.set_wall((0, 0), Direction::Right, false);
+        assert!(!maze.has_wall((1, 0), Direction::Left));
+    }
+}
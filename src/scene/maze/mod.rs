@@ -0,0 +1,402 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+use rand::{Rng, RngCore};
+use rapier2d::{math::Point, na};
+use serde::{Deserialize, Serialize};
+
+use generator::{Generator, Kruskal, Prim, RecursiveBacktracker, Wilson};
+
+pub mod file;
+pub mod generator;
+pub mod path;
+pub mod spawn;
+
+/// A cell of the maze, in `(x, y)`. The cell `(0, 0)` is at the bottom left corner.
+pub type Cell = (usize, usize);
+
+/// Walls are drawn as rectangles twice as thick as this.
+const LINE_THICKNESS: f32 = 1.0 / 16.0;
+
+/// A 3x2 maze for tests, a wall separates `(0, 0)` from `(1, 0)` and
+/// `(1, 0)` from `(1, 1)`, the other cells are open to each other.
+#[cfg(test)]
+pub(crate) const ARENA: &str = "\
++-+-+-+
+|     |
++ +-+ +
+| |   |
++-+-+-+
+";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Maze {
+    pub width: usize,
+    pub height: usize,
+    /// `horizontal_walls[y][x]` is the wall at the bottom of cell `(x, y)`,
+    /// there are `height + 1` rows and each row has `width` walls.
+    horizontal_walls: Vec<Vec<bool>>,
+    /// `vertical_walls[y][x]` is the wall at the left of cell `(x, y)`,
+    /// there are `height` rows and each row has `width + 1` walls.
+    vertical_walls: Vec<Vec<bool>>,
+}
+
+pub trait TriangleIndexList<T> {
+    fn new() -> Self;
+    fn push(&mut self, p0: T, p1: T, p2: T);
+}
+
+pub trait VertexList<T>: Sized {
+    fn new() -> Self;
+    fn with_capacity(_capacity: usize) -> Self {
+        Self::new()
+    }
+    fn push(&mut self, p0: T, p1: T);
+}
+
+impl Maze {
+    /// Create a new std maze with specified Rng, with room for the players.
+    /// It grows by a column for every player and by a row for every two players beyond two.
+    pub fn new<R: Rng>(players: usize, rng: &mut R) -> Maze {
+        let extra = players.saturating_sub(2);
+        let width = rng.gen_range(4 + extra..13 + extra);
+        let height = rng.gen_range(4 + extra / 2..11 + extra / 2);
+        let braid = rng.gen_range(0.2..0.6);
+
+        let generators: [&dyn Generator; 4] = [&RecursiveBacktracker, &Kruskal, &Prim, &Wilson];
+        let generator = generators[rng.gen_range(0..generators.len())];
+        let maze = Maze::generate(width, height, generator, braid, rng);
+        debug!("Created maze: [{}, {}]", width, height);
+        maze
+    }
+
+    /// Create a maze of the given size, every cell of it is closed by four walls.
+    pub fn closed(width: usize, height: usize) -> Maze {
+        Maze {
+            width,
+            height,
+            horizontal_walls: vec![vec![true; width]; height + 1],
+            vertical_walls: vec![vec![true; width + 1]; height],
+        }
+    }
+
+    /// Create a maze of the given size, there are walls only around the maze.
+    pub fn empty(width: usize, height: usize) -> Maze {
+        let mut maze = Maze::closed(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                maze.set_wall((x, y), Direction::Top, false);
+                maze.set_wall((x, y), Direction::Right, false);
+            }
+        }
+        maze
+    }
+
+    /// Create a maze of another size, keeping the walls of the overlapped part.
+    pub fn resized(&self, width: usize, height: usize) -> Maze {
+        let mut maze = Maze::empty(width, height);
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                for &direction in [Direction::Top, Direction::Right].iter() {
+                    if self.neighbour((x, y), direction).is_some() {
+                        maze.set_wall((x, y), direction, self.has_wall((x, y), direction));
+                    }
+                }
+            }
+        }
+        maze
+    }
+
+    /// Carve a closed maze with the generator, then remove dead ends with the
+    /// possibility of `braid`. All cells are guaranteed to be reachable.
+    pub fn generate(
+        width: usize,
+        height: usize,
+        generator: &dyn Generator,
+        braid: f64,
+        rng: &mut dyn RngCore,
+    ) -> Maze {
+        let mut maze = Maze::closed(width, height);
+        generator.carve(&mut maze, rng);
+        generator::braid(&mut maze, braid, rng);
+        debug_assert!(maze.is_connected());
+        maze
+    }
+
+    /// Get the cell next to `cell` in the direction, `None` if it's out of the maze.
+    pub fn neighbour(&self, (x, y): Cell, direction: Direction) -> Option<Cell> {
+        match direction {
+            Direction::Top if y + 1 < self.height => Some((x, y + 1)),
+            Direction::Right if x + 1 < self.width => Some((x + 1, y)),
+            Direction::Bottom if y > 0 => Some((x, y - 1)),
+            Direction::Left if x > 0 => Some((x - 1, y)),
+            _ => None,
+        }
+    }
+
+    pub fn has_wall(&self, (x, y): Cell, direction: Direction) -> bool {
+        match direction {
+            Direction::Top => self.horizontal_walls[y + 1][x],
+            Direction::Right => self.vertical_walls[y][x + 1],
+            Direction::Bottom => self.horizontal_walls[y][x],
+            Direction::Left => self.vertical_walls[y][x],
+        }
+    }
+
+    /// Build or remove the wall at one side of the cell.
+    /// Walls around the maze can't be removed, so this is ignored for them.
+    pub fn set_wall(&mut self, cell: Cell, direction: Direction, wall: bool) {
+        if self.neighbour(cell, direction).is_none() {
+            return;
+        }
+        let (x, y) = cell;
+        match direction {
+            Direction::Top => self.horizontal_walls[y + 1][x] = wall,
+            Direction::Right => self.vertical_walls[y][x + 1] = wall,
+            Direction::Bottom => self.horizontal_walls[y][x] = wall,
+            Direction::Left => self.vertical_walls[y][x] = wall,
+        }
+    }
+
+    /// Iterate all cells which can be reached from `cell` directly.
+    pub fn open_neighbours(&self, cell: Cell) -> impl Iterator<Item = Cell> + '_ {
+        Direction::ALL
+            .iter()
+            .filter(move |&&direction| !self.has_wall(cell, direction))
+            .filter_map(move |&direction| self.neighbour(cell, direction))
+    }
+
+    /// Check if every cell of the maze can be reached from any other cell.
+    pub fn is_connected(&self) -> bool {
+        self.distances_from((0, 0)).reachable().count() == self.width * self.height
+    }
+
+    /// Merge walls in a line into rectangles, in the physic world.
+    /// Every rectangle is longer than its walls by half of the thickness at both ends,
+    /// so there is no gap at corners where walls meet.
+    pub fn wall_rects(&self) -> Vec<WallRect> {
+        let half_width = self.width as f32 / 2.0;
+        let half_height = self.height as f32 / 2.0;
+        let mut rects = Vec::new();
+        // Find runs of `true` in a row of walls, returns the begin and the end of each run.
+        let runs = |walls: &mut dyn Iterator<Item = bool>| {
+            let mut runs = Vec::new();
+            let mut begin = None;
+            for (i, wall) in walls.chain(std::iter::once(false)).enumerate() {
+                match (wall, begin) {
+                    (true, None) => begin = Some(i),
+                    (false, Some(b)) => {
+                        runs.push((b as f32, i as f32));
+                        begin = None;
+                    }
+                    _ => {}
+                }
+            }
+            runs
+        };
+        for (y, row) in self.horizontal_walls.iter().enumerate() {
+            let y = y as f32 - half_height;
+            for (x0, x1) in runs(&mut row.iter().copied()) {
+                rects.push(WallRect {
+                    min: Point::new(x0 - half_width - LINE_THICKNESS, y - LINE_THICKNESS),
+                    max: Point::new(x1 - half_width + LINE_THICKNESS, y + LINE_THICKNESS),
+                });
+            }
+        }
+        for x in 0..=self.width {
+            let column = &mut self.vertical_walls.iter().map(|row| row[x]);
+            let x = x as f32 - half_width;
+            for (y0, y1) in runs(column) {
+                rects.push(WallRect {
+                    min: Point::new(x - LINE_THICKNESS, y0 - half_height - LINE_THICKNESS),
+                    max: Point::new(x + LINE_THICKNESS, y1 - half_height + LINE_THICKNESS),
+                });
+            }
+        }
+        rects
+    }
+
+    /// Two triangles for each of [`Maze::wall_rects`].
+    pub fn triangle_mesh<V, I>(&self) -> (V, I)
+        where
+            V: VertexList<f32>,
+            I: TriangleIndexList<u32>,
+    {
+        let rects = self.wall_rects();
+        let mut vertices = V::with_capacity(rects.len() * 4);
+        let mut indexes = I::new();
+        for (i, rect) in rects.iter().enumerate() {
+            vertices.push(rect.min.x, rect.min.y);
+            vertices.push(rect.max.x, rect.min.y);
+            vertices.push(rect.min.x, rect.max.y);
+            vertices.push(rect.max.x, rect.max.y);
+            let offset = i as u32 * 4;
+            indexes.push(offset, offset + 1, offset + 3);
+            indexes.push(offset, offset + 3, offset + 2);
+        }
+        (vertices, indexes)
+    }
+}
+
+/// An axis-aligned rectangle of walls, see [`Maze::wall_rects`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallRect {
+    pub min: Point<f32>,
+    pub max: Point<f32>,
+}
+
+impl WallRect {
+    pub fn center(&self) -> Point<f32> {
+        na::center(&self.min, &self.max)
+    }
+
+    pub fn half_extents(&self) -> (f32, f32) {
+        ((self.max.x - self.min.x) / 2.0, (self.max.y - self.min.y) / 2.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Direction {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Top,
+        Direction::Right,
+        Direction::Bottom,
+        Direction::Left,
+    ];
+}
+
+pub(crate) mod util {
+    use rapier2d::math::Point;
+
+    use crate::scene::game_scene::Vertex;
+
+    use super::{TriangleIndexList, VertexList};
+
+    impl TriangleIndexList<u32> for Vec<u32> {
+        fn new() -> Self {
+            Vec::new()
+        }
+
+        fn push(&mut self, p0: u32, p1: u32, p2: u32) {
+            self.push(p0);
+            self.push(p1);
+            self.push(p2);
+        }
+    }
+
+    impl VertexList<f32> for Vec<Vertex> {
+        fn new() -> Self {
+            Vec::new()
+        }
+
+        fn with_capacity(capacity: usize) -> Self {
+            Vec::with_capacity(capacity)
+        }
+
+        fn push(&mut self, p0: f32, p1: f32) {
+            self.push(Vertex::new(p0, p1));
+        }
+    }
+
+    impl TriangleIndexList<u32> for Vec<[u32; 3]> {
+        fn new() -> Self {
+            Vec::new()
+        }
+
+        fn push(&mut self, p0: u32, p1: u32, p2: u32) {
+            self.push([p0, p1, p2]);
+        }
+    }
+
+    impl VertexList<f32> for Vec<Point<f32>> {
+        fn new() -> Self {
+            Vec::new()
+        }
+
+        fn with_capacity(capacity: usize) -> Self {
+            Vec::with_capacity(capacity)
+        }
+
+        fn push(&mut self, p0: f32, p1: f32) {
+            self.push(Point::new(p0, p1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use rapier2d::math::Point;
+
+    use super::{Direction, LINE_THICKNESS, Maze, VertexList};
+
+    impl VertexList<f32> for Vec<(f32, f32)> {
+        fn new() -> Self {
+            Vec::new()
+        }
+
+        fn push(&mut self, p0: f32, p1: f32) {
+            self.push((p0, p1));
+        }
+    }
+
+    fn mesh(maze: &Maze) -> (Vec<(f32, f32)>, Vec<[u32; 3]>) {
+        maze.triangle_mesh()
+    }
+
+    #[test]
+    fn same_seed_same_mesh() {
+        for seed in 0..20 {
+            let maze1 = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(seed));
+            let maze2 = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(seed));
+            assert_eq!(mesh(&maze1), mesh(&maze2), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn different_seeds_different_mesh() {
+        let maze1 = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(1));
+        let maze2 = Maze::new(2, &mut ChaCha8Rng::seed_from_u64(2));
+        assert_ne!(mesh(&maze1), mesh(&maze2));
+    }
+
+    #[test]
+    fn more_players_bigger_maze() {
+        for seed in 0..20 {
+            let maze = Maze::new(8, &mut ChaCha8Rng::seed_from_u64(seed));
+            assert!(maze.width >= 10 && maze.height >= 7, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn closed_maze_mesh() {
+        // Walls in a line are merged, a 3x2 maze has 3 rows and 4 columns of walls.
+        let (vertices, indexes) = mesh(&Maze::closed(3, 2));
+        assert_eq!(vertices.len(), 4 * (3 + 4));
+        assert_eq!(indexes.len(), 2 * (3 + 4));
+    }
+
+    #[test]
+    fn wall_rects_are_merged() {
+        let mut maze = Maze::empty(3, 2);
+        maze.set_wall((0, 0), Direction::Top, true);
+        maze.set_wall((2, 0), Direction::Top, true);
+        let rects = maze.wall_rects();
+        // The broken middle row, the two borders of rows and the two borders of columns.
+        assert_eq!(rects.len(), 6);
+        let border = rects[0];
+        assert_eq!(border.center(), Point::new(0.0, -1.0));
+        assert_eq!(border.half_extents(), (1.5 + LINE_THICKNESS, LINE_THICKNESS));
+        let left = rects[4];
+        assert_eq!(left.min, Point::new(-1.5 - LINE_THICKNESS, -1.0 - LINE_THICKNESS));
+        assert_eq!(left.max, Point::new(-1.5 + LINE_THICKNESS, 1.0 + LINE_THICKNESS));
+    }
+}
@@ -0,0 +1,245 @@
+//! Query the maze as a graph, whose nodes are cells and edges are passages without walls.
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use rapier2d::math::Point;
+
+use super::{Cell, Direction, Maze};
+
+/// Length of the shortest path from a source cell to every cell,
+/// `None` for the cells which can't be reached.
+pub struct DistanceField {
+    distances: Vec<Vec<Option<usize>>>,
+}
+
+impl Maze {
+    /// Iterate all cells, row by row from the bottom.
+    pub fn cells(&self) -> impl Iterator<Item = Cell> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    /// Iterate all cells next to `cell`, ignoring the walls.
+    pub fn neighbours(&self, cell: Cell) -> impl Iterator<Item = (Direction, Cell)> + '_ {
+        Direction::ALL
+            .iter()
+            .filter_map(move |&d| self.neighbour(cell, d).map(|next| (d, next)))
+    }
+
+    /// The direction from `from` to `to`, `None` if they are not next to each other.
+    pub fn direction_between(&self, from: Cell, to: Cell) -> Option<Direction> {
+        self.neighbours(from)
+            .find(|&(_, next)| next == to)
+            .map(|(direction, _)| direction)
+    }
+
+    /// Whether there is a wall between two cells, `None` if they are not next to each other.
+    pub fn wall_between(&self, from: Cell, to: Cell) -> Option<bool> {
+        self.direction_between(from, to)
+            .map(|direction| self.has_wall(from, direction))
+    }
+
+    /// Breadth-first search from `source` to all cells.
+    pub fn distances_from(&self, source: Cell) -> DistanceField {
+        let mut distances = vec![vec![None; self.width]; self.height];
+        let mut queue = VecDeque::new();
+        distances[source.1][source.0] = Some(0);
+        queue.push_back((source, 0));
+        while let Some((cell, distance)) = queue.pop_front() {
+            for (x, y) in self.open_neighbours(cell) {
+                if distances[y][x].is_none() {
+                    distances[y][x] = Some(distance + 1);
+                    queue.push_back(((x, y), distance + 1));
+                }
+            }
+        }
+        DistanceField { distances }
+    }
+
+    /// Split the cells into areas, cells of different areas can't reach each other.
+    pub fn areas(&self) -> Vec<Vec<Cell>> {
+        let mut visited = vec![vec![false; self.width]; self.height];
+        let mut areas = Vec::new();
+        for (x, y) in self.cells() {
+            if visited[y][x] {
+                continue;
+            }
+            let area: Vec<Cell> = self.distances_from((x, y)).reachable().map(|(c, _)| c).collect();
+            for &(x, y) in area.iter() {
+                visited[y][x] = true;
+            }
+            areas.push(area);
+        }
+        areas
+    }
+
+    /// One of the shortest paths found by breadth-first search, including both ends.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn shortest_path(&self, from: Cell, to: Cell) -> Option<Vec<Cell>> {
+        // Search from the end, so the path can be followed from the start.
+        self.distances_from(to).path_from(self, from)
+    }
+
+    /// One of the shortest paths found by A* with the manhattan distance, including both ends.
+    /// This visits fewer cells than [`Maze::shortest_path`] when the cells are close.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn a_star(&self, from: Cell, to: Cell) -> Option<Vec<Cell>> {
+        let heuristic = |(x, y): Cell| x.max(to.0) - x.min(to.0) + y.max(to.1) - y.min(to.1);
+        let mut costs = vec![vec![usize::MAX; self.width]; self.height];
+        let mut came_from = vec![vec![None; self.width]; self.height];
+        let mut open = BinaryHeap::new();
+        costs[from.1][from.0] = 0;
+        open.push(Reverse((heuristic(from), 0, from)));
+
+        while let Some(Reverse((_, cost, cell))) = open.pop() {
+            if cell == to {
+                let mut path = vec![to];
+                let mut cell = to;
+                while let Some(previous) = came_from[cell.1][cell.0] {
+                    path.push(previous);
+                    cell = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost > costs[cell.1][cell.0] {
+                // Outdated entry, a shorter way to this cell has been found.
+                continue;
+            }
+            for (x, y) in self.open_neighbours(cell) {
+                if cost + 1 < costs[y][x] {
+                    costs[y][x] = cost + 1;
+                    came_from[y][x] = Some(cell);
+                    open.push(Reverse((cost + 1 + heuristic((x, y)), cost + 1, (x, y))));
+                }
+            }
+        }
+        None
+    }
+
+    /// The cell containing the point of the physic world, `None` if it's out of the maze.
+    /// The maze is centred at the origin, the same as [`Maze::triangle_mesh`].
+    pub fn cell_at(&self, point: &Point<f32>) -> Option<Cell> {
+        let x = point.x + self.width as f32 / 2.0;
+        let y = point.y + self.height as f32 / 2.0;
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+
+    /// The center of a cell in the physic world.
+    pub fn cell_center(&self, (x, y): Cell) -> Point<f32> {
+        Point::new(
+            x as f32 + 0.5 - self.width as f32 / 2.0,
+            y as f32 + 0.5 - self.height as f32 / 2.0,
+        )
+    }
+}
+
+impl DistanceField {
+    pub fn get(&self, (x, y): Cell) -> Option<usize> {
+        self.distances[y][x]
+    }
+
+    /// Iterate all reachable cells with their distances.
+    pub fn reachable(&self) -> impl Iterator<Item = (Cell, usize)> + '_ {
+        self.distances.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter_map(move |(x, distance)| distance.map(|d| ((x, y), d)))
+        })
+    }
+
+    /// The next cell to go from `cell` towards the source, `None` if it's the source
+    /// or can't reach the source.
+    pub fn next_step(&self, maze: &Maze, cell: Cell) -> Option<Cell> {
+        let distance = self.get(cell)?;
+        maze.open_neighbours(cell)
+            .find(|&next| self.get(next) == distance.checked_sub(1))
+    }
+
+    /// Follow the field from `cell` to the source, including both ends.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn path_from(&self, maze: &Maze, cell: Cell) -> Option<Vec<Cell>> {
+        self.get(cell)?;
+        let mut path = vec![cell];
+        while let Some(next) = self.next_step(maze, *path.last().unwrap()) {
+            path.push(next);
+        }
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::scene::maze::ARENA;
+
+    use super::*;
+
+    #[test]
+    fn walls_between_cells() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        assert_eq!(maze.wall_between((0, 0), (1, 0)), Some(true));
+        assert_eq!(maze.wall_between((1, 0), (2, 0)), Some(false));
+        assert_eq!(maze.wall_between((0, 0), (0, 1)), Some(false));
+        assert_eq!(maze.wall_between((0, 0), (1, 1)), None);
+        assert_eq!(maze.direction_between((2, 0), (2, 1)), Some(Direction::Top));
+    }
+
+    #[test]
+    fn paths_in_arena() {
+        let maze = Maze::from_ascii(ARENA).unwrap();
+        let expected = vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 0), (1, 0)];
+        assert_eq!(maze.shortest_path((0, 0), (1, 0)), Some(expected.clone()));
+        assert_eq!(maze.a_star((0, 0), (1, 0)), Some(expected));
+        assert_eq!(maze.shortest_path((2, 1), (2, 1)), Some(vec![(2, 1)]));
+
+        let field = maze.distances_from((0, 0));
+        assert_eq!(field.get((1, 0)), Some(5));
+        assert_eq!(field.reachable().count(), 6);
+        assert_eq!(field.next_step(&maze, (1, 1)), Some((0, 1)));
+    }
+
+    #[test]
+    fn unreachable_cells() {
+        let mut maze = Maze::empty(3, 1);
+        maze.set_wall((0, 0), Direction::Right, true);
+        assert_eq!(maze.shortest_path((0, 0), (2, 0)), None);
+        assert_eq!(maze.a_star((0, 0), (2, 0)), None);
+        assert_eq!(maze.distances_from((2, 0)).get((0, 0)), None);
+    }
+
+    #[test]
+    fn a_star_is_as_short_as_bfs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..20 {
+            let maze = Maze::new(2, &mut rng);
+            let to = (maze.width - 1, maze.height - 1);
+            let bfs = maze.shortest_path((0, 0), to).unwrap();
+            let a_star = maze.a_star((0, 0), to).unwrap();
+            assert_eq!(bfs.len(), a_star.len());
+            for path in [bfs, a_star].iter() {
+                for step in path.windows(2) {
+                    assert_eq!(maze.wall_between(step[0], step[1]), Some(false));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn world_to_cell() {
+        let maze = Maze::closed(4, 3);
+        assert_eq!(maze.cell_at(&Point::new(-2.0, -1.5)), Some((0, 0)));
+        assert_eq!(maze.cell_at(&Point::new(1.9, 1.4)), Some((3, 2)));
+        assert_eq!(maze.cell_at(&Point::new(2.1, 0.0)), None);
+        for cell in maze.cells() {
+            assert_eq!(maze.cell_at(&maze.cell_center(cell)), Some(cell));
+        }
+    }
+}
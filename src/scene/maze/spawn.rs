@@ -0,0 +1,120 @@
+//! Choose where tanks start a round.
+use std::f32::consts::TAU;
+
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+use super::{Cell, Maze};
+
+/// The path distance between every two tanks is at least this if the maze is big enough.
+pub const MIN_SPAWN_DISTANCE: usize = 4;
+/// How many random placements are tried before lowering the distance.
+const ATTEMPTS: usize = 32;
+
+/// Pick a cell and an orientation for each of `count` tanks.
+///
+/// Tanks are placed at the center of the cells, where a tank never overlaps a wall
+/// whatever its orientation is. Each tank gets its own cell in the largest connected area,
+/// keeping the path distance between tanks as close to [`MIN_SPAWN_DISTANCE`] as possible.
+/// Cells are only shared when the area has fewer cells than tanks.
+pub fn spawn_points(maze: &Maze, count: usize, rng: &mut dyn RngCore) -> Vec<(Cell, f32)> {
+    let area = maze.areas().into_iter().max_by_key(Vec::len).unwrap_or_default();
+    let cells = (1..=MIN_SPAWN_DISTANCE)
+        .rev()
+        .find_map(|distance| {
+            (0..ATTEMPTS).find_map(|_| try_place(maze, &area, count, distance, rng))
+        })
+        .unwrap_or_else(|| area.iter().copied().cycle().take(count).collect());
+    cells
+        .into_iter()
+        .map(|cell| (cell, rng.gen_range(0.0..TAU)))
+        .collect()
+}
+
+/// Place tanks one by one at random cells far enough from the placed ones.
+fn try_place(
+    maze: &Maze,
+    area: &[Cell],
+    count: usize,
+    distance: usize,
+    rng: &mut dyn RngCore,
+) -> Option<Vec<Cell>> {
+    let mut candidates = area.to_vec();
+    let mut cells = Vec::with_capacity(count);
+    while cells.len() < count {
+        let &cell = candidates.choose(rng)?;
+        let field = maze.distances_from(cell);
+        candidates.retain(|&c| field.get(c).map_or(false, |d| d >= distance));
+        cells.push(cell);
+    }
+    Some(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::scene::maze::Direction;
+
+    use super::*;
+
+    fn min_distance(maze: &Maze, cells: &[Cell]) -> usize {
+        cells
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &a)| {
+                let field = maze.distances_from(a);
+                cells[i + 1..].iter().map(move |&b| field.get(b).unwrap())
+            })
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn tanks_are_far_from_each_other() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            let maze = Maze::new(2, &mut rng);
+            for count in 2..=8 {
+                let spawns = spawn_points(&maze, count, &mut rng);
+                assert_eq!(spawns.len(), count);
+                let cells: Vec<Cell> = spawns.iter().map(|&(cell, _)| cell).collect();
+                assert!(min_distance(&maze, &cells) >= 1);
+                for &(_, angle) in spawns.iter() {
+                    assert!((0.0..TAU).contains(&angle));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn min_distance_is_kept_in_open_maze() {
+        let maze = Maze::empty(10, 8);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for count in 2..=8 {
+            let spawns = spawn_points(&maze, count, &mut rng);
+            let cells: Vec<Cell> = spawns.iter().map(|&(cell, _)| cell).collect();
+            assert!(min_distance(&maze, &cells) >= MIN_SPAWN_DISTANCE);
+        }
+    }
+
+    #[test]
+    fn sealed_cells_are_avoided() {
+        let mut maze = Maze::empty(4, 4);
+        for direction in Direction::ALL.iter() {
+            maze.set_wall((0, 0), *direction, true);
+        }
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        for _ in 0..20 {
+            let spawns = spawn_points(&maze, 4, &mut rng);
+            assert!(spawns.iter().all(|&(cell, _)| cell != (0, 0)));
+        }
+    }
+
+    #[test]
+    fn tiny_maze_shares_cells() {
+        let maze = Maze::empty(1, 2);
+        let spawns = spawn_points(&maze, 3, &mut ChaCha8Rng::seed_from_u64(3));
+        assert_eq!(spawns.len(), 3);
+    }
+}
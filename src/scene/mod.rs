@@ -1,10 +1,17 @@
 use crate::input::input_center::InputCenter;
 
 // pub mod main_menu;
+pub(crate) mod client_scene;
+pub(crate) mod editor_scene;
 pub mod game_scene;
-mod maze;
+pub(crate) mod lobby_scene;
+pub(crate) mod maze;
+pub(crate) mod palette;
+pub(crate) mod peer_scene;
 pub(crate) mod prepare_scene;
+pub(crate) mod replay_scene;
 mod render_layer;
+mod slots;
 
 pub trait SceneRender {
     fn render(
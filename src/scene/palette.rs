@@ -0,0 +1,75 @@
+//! Colours of players, every player of a match has a different one.
+
+pub struct Colour {
+    pub name: &'static str,
+    pub rgb: [f32; 3],
+}
+
+pub const COLOURS: [Colour; 8] = [
+    Colour {
+        name: "red",
+        rgb: [0.90, 0.22, 0.21],
+    },
+    Colour {
+        name: "green",
+        rgb: [0.30, 0.69, 0.31],
+    },
+    Colour {
+        name: "blue",
+        rgb: [0.13, 0.59, 0.95],
+    },
+    Colour {
+        name: "yellow",
+        rgb: [0.98, 0.80, 0.10],
+    },
+    Colour {
+        name: "purple",
+        rgb: [0.61, 0.15, 0.69],
+    },
+    Colour {
+        name: "orange",
+        rgb: [1.00, 0.55, 0.00],
+    },
+    Colour {
+        name: "cyan",
+        rgb: [0.00, 0.74, 0.83],
+    },
+    Colour {
+        name: "pink",
+        rgb: [0.93, 0.38, 0.62],
+    },
+];
+
+/// Every player has a different colour.
+pub const MAX_PLAYERS: usize = COLOURS.len();
+
+impl Colour {
+    pub fn rgba(&self) -> [f32; 4] {
+        [self.rgb[0], self.rgb[1], self.rgb[2], 1.0]
+    }
+}
+
+/// `wanted` if no one has taken it, or the first colour no one has taken.
+pub fn free_colour(taken: &[usize], wanted: usize) -> usize {
+    if wanted < COLOURS.len() && !taken.contains(&wanted) {
+        return wanted;
+    }
+    (0..COLOURS.len())
+        .find(|colour| !taken.contains(colour))
+        .unwrap_or(0)
+}
+
+/// The next colour from `current` in the direction, skipping taken ones.
+pub fn next_colour(taken: &[usize], current: usize, forward: bool) -> usize {
+    let len = COLOURS.len();
+    (1..len)
+        .map(|step| if forward { current + step } else { current + len - step })
+        .map(|colour| colour % len)
+        .find(|colour| !taken.contains(colour))
+        .unwrap_or(current)
+}
+
+/// The colour of the player, picked in `colours` or the one of its index if not.
+pub fn player_colour(colours: &[usize], player: usize) -> usize {
+    colours.get(player).copied().unwrap_or(player % COLOURS.len())
+}
@@ -0,0 +1,143 @@
+//! A match without a server, every peer simulates it with rollback.
+use std::{error::Error, net::SocketAddr, time};
+
+use crossbeam_channel::tick;
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+
+use crate::input::{Controller, input_center::InputCenter, WorldView};
+use crate::net::{peer::Peer, rollback::Rollback};
+
+use super::{
+    game_scene::{
+        self, maze_layer::MazeData, simulation::Simulation, FrameData, GameSceneRender,
+        RenderChannels, WallColliders, PHYSICAL_DT,
+    },
+    maze::Maze,
+    SceneRender, SceneUpdater,
+};
+
+/// Ticks between two logs of the connection quality.
+const QUALITY_LOG_INTERVAL: u32 = (5.0 / PHYSICAL_DT) as u32;
+
+pub struct PeerSceneUpdater {
+    /// Addresses of all peers in the order of their players.
+    addresses: Vec<SocketAddr>,
+    /// Index of the local player.
+    player: usize,
+    /// All peers have to simulate the same match.
+    seed: u64,
+    arena: Option<Maze>,
+    colliders: WallColliders,
+    controller: Box<dyn Controller>,
+    channels: RenderChannels,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn new(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    addresses: Vec<SocketAddr>,
+    player: usize,
+    seed: u64,
+    arena: Option<Maze>,
+    colliders: WallColliders,
+    controller: Box<dyn Controller>,
+) -> (GameSceneRender, PeerSceneUpdater) {
+    info!("Playing as player {} of {} peers with seed {}", player, addresses.len(), seed);
+    let (render, channels) = game_scene::render(device, format);
+    (
+        render,
+        PeerSceneUpdater {
+            addresses,
+            player,
+            seed,
+            arena,
+            colliders,
+            controller,
+            channels,
+        },
+    )
+}
+
+impl PeerSceneUpdater {
+    fn manage(&self, input_center: &InputCenter) -> Result<(), Box<dyn Error>> {
+        let mut peer = Peer::bind(self.addresses.clone(), self.player)?;
+        let simulation = Simulation::new(
+            self.addresses.len(),
+            self.seed,
+            self.arena.clone(),
+            self.colliders,
+        );
+        let mut rollback = Rollback::new(simulation);
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        // The round whose maze has been sent to the render.
+        let mut round = 0;
+        let mut ticks = 0u32;
+
+        loop {
+            input_center.update();
+            peer.receive(&mut rollback)?;
+
+            let frame = if rollback.stalled() {
+                self.check_peers(&peer, &rollback)?;
+                None
+            } else {
+                let simulation = rollback.simulation();
+                let tanks = simulation.tank_poses();
+                self.controller.observe(&WorldView {
+                    maze: simulation.maze(),
+                    tanks: &tanks,
+                    me: self.player,
+                });
+                peer.input(&mut rollback, self.controller.input());
+                // Never stalled here, the local input can't make it wait.
+                let snapshot = rollback.advance().unwrap();
+                if snapshot.round != round {
+                    round = snapshot.round;
+                    let maze = MazeData::from(rollback.simulation().maze());
+                    self.channels.maze_update_sender.send(maze)?;
+                }
+                // Peers don't tell their colours, tanks take the ones of their places.
+                Some(FrameData::new(&snapshot, &[]))
+            };
+            peer.send(&rollback)?;
+
+            ticks += 1;
+            if ticks % QUALITY_LOG_INTERVAL == 0 {
+                for player in (0..self.addresses.len()).filter(|&p| p != peer.player()) {
+                    debug!("Connection to player {}: {}", player, peer.quality(player));
+                }
+            }
+            if !self.channels.wait_tick(&ticker, frame)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Waiting for a peer which has left would never end.
+    fn check_peers(&self, peer: &Peer, rollback: &Rollback) -> Result<(), Box<dyn Error>> {
+        for player in (0..self.addresses.len()).filter(|&p| p != peer.player()) {
+            // Peers may start later than this one.
+            if rollback.known_until(player) > 0 && !peer.quality(player).connected {
+                return Err(format!("Player {} stopped answering", player).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SceneUpdater for PeerSceneUpdater {
+    fn update(
+        &self,
+        _device: &wgpu::Device,
+        _format: wgpu::TextureFormat,
+        input_center: &InputCenter,
+    ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
+        // There's no lobby to go back to without a server,
+        // the window closes when the update thread stops.
+        self.manage(input_center)
+            .unwrap_or_else(|err| error!("{}", err));
+        None
+    }
+}
@@ -1,103 +1,388 @@
-use std::{error::Error, sync::Arc};
+use std::{error::Error, time};
 
-use gilrs::{Event, EventType};
+use crossbeam_channel::{Sender, tick};
+use gilrs::{Event, EventType, GamepadId};
 #[allow(unused_imports)]
 use log::{debug, error, info, log_enabled};
-use wgpu::{Device, Queue, SwapChainError, SwapChainTexture};
 use winit::event::{ElementState, VirtualKeyCode};
 
-use crate::input::{Controller, input_center::InputCenter};
+use crate::input::{
+    Controller,
+    input_center::{EventKind, InputCenter, Source},
+    PlayerInput,
+};
+use crate::options::Options;
 
-use super::{game_scene, SceneRender, SceneUpdater};
+use super::{
+    game_scene::{self, PHYSICAL_DT},
+    palette::{self, COLOURS, MAX_PLAYERS},
+    peer_scene,
+    slots::{
+        self, SlotsFrame, SlotsRender, ALERT, BACKGROUND, DARK, LIGHT, READY, SLOT_SIZE, VIEW_SIZE,
+    },
+    SceneRender, SceneUpdater,
+};
 
+/// Seconds from everyone being ready to the start of the match.
+const COUNTDOWN: f32 = 3.0;
+/// Ticks a disconnected gamepad blinks on or off.
+const BLINK_TICKS: u32 = 45;
+
+#[derive(Clone, Copy, PartialEq)]
 enum ControllerStatus {
+    /// Ready to play, firing again cancels it.
     Prepared,
+    /// The gamepad is disconnected, the match waits for it to come back.
     Unknown,
+    /// Joined, turning picks a colour, firing gets ready and pulling back leaves.
     Unprepared,
 }
 
 struct Player {
     controller: Box<dyn Controller>,
+    source: Source,
+    /// The keyboard cluster, the gamepad or the bot.
+    name: String,
     status: ControllerStatus,
+    /// Index in [`COLOURS`], players have different colours.
+    colour: usize,
+    /// Changes of the input pick colours, toggle ready and leave.
+    last_input: PlayerInput,
 }
 
-pub struct PrepareSceneRender {}
+pub struct PrepareSceneUpdater {
+    options: Options,
 
-pub struct PrepareSceneUpdater {}
+    frame_update_sender: Sender<SlotsFrame>,
+}
 
 pub fn new(
-    _device: Arc<wgpu::Device>,
-    _format: wgpu::TextureFormat,
-) -> (PrepareSceneRender, PrepareSceneUpdater) {
-    (PrepareSceneRender {}, PrepareSceneUpdater {})
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    options: Options,
+) -> (SlotsRender, PrepareSceneUpdater) {
+    let (render, frame_update_sender) = slots::render(device, format);
+    (
+        render,
+        PrepareSceneUpdater {
+            options,
+            frame_update_sender,
+        },
+    )
 }
 
 impl PrepareSceneUpdater {
+    /// Gather players until everyone is ready and the countdown ends.
     fn manage(&self, input_center: &InputCenter) -> Result<Vec<Player>, Box<dyn Error>> {
-        use std::cell::RefCell;
-        let players = RefCell::new(vec![]);
-        while players.borrow().len() < 2 {
-            input_center
-                .update(
-                    |event| {
-                        let players = &mut *players.borrow_mut();
-                        if let winit::event::KeyboardInput {
-                            state: ElementState::Pressed,
-                            virtual_keycode,
-                            ..
-                        } = *event
-                        {
-                            match virtual_keycode {
-                                Some(VirtualKeyCode::Q) => {
-                                    debug!("New player: {}", "Q");
-                                    players.push(Player {
-                                        controller: Box::new(input_center.create_controller_red()),
-                                        status: ControllerStatus::Prepared,
-                                    })
-                                }
-                                Some(VirtualKeyCode::M) => {
-                                    debug!("New player: {}", "M");
-                                    players.push(Player {
-                                        controller: Box::new(input_center.create_controller_green()),
-                                        status: ControllerStatus::Prepared,
-                                    })
-                                }
-                                _ => {}
-                            }
-                        }
-                    },
-                    |gilrs, event| {
-                        let players = &mut *players.borrow_mut();
-                        if let Event {
-                            id,
-                            event: EventType::ButtonPressed(gilrs::Button::South, ..),
-                            ..
-                        } = *event
-                        {
-                            debug!("New player: {}", gilrs.gamepad(id).name());
-                            players.push(Player {
-                                controller: Box::new(input_center.create_gamepad_controller(id)),
-                                status: ControllerStatus::Prepared,
-                            })
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        let mut players: Vec<Player> = Vec::new();
+        // Other players of a LAN match join from their own computers.
+        let (least, most) = match self.options.peers {
+            Some(_) => (1, 1),
+            None => (self.options.players.unwrap_or(2), MAX_PLAYERS),
+        };
+        let ticks_per_second = (1.0 / PHYSICAL_DT).round() as u32;
+        let countdown_ticks = (COUNTDOWN / PHYSICAL_DT).round() as u32;
+        let mut countdown: Option<u32> = None;
+        let mut ticks = 0u32;
+        let fire_keys: Vec<String> = input_center
+            .key_bindings()
+            .clusters
+            .iter()
+            .map(|cluster| cluster.fire.to_string())
+            .collect();
+        let instructions = format!(
+            "Press {} or the south button to join, 1 2 3 add bots\n\
+             Turn to pick a colour, fire again to get ready\n\
+             Pull back to leave",
+            fire_keys.join(" "),
+        );
+        info!("{}", instructions.replace('\n', ", "));
+        let frame = draw(&players, most, None, true, &instructions);
+        self.frame_update_sender.send(frame)?;
+        loop {
+            ticker.recv()?;
+            let mut changed = false;
+            for event in input_center.update().events {
+                match event.kind {
+                    EventKind::Gamepad(Event { id, event: EventType::Disconnected, .. }) => {
+                        changed |= connect(&mut players, id, false);
+                    }
+                    EventKind::Gamepad(Event { id, event: EventType::Connected, .. }) => {
+                        changed |= connect(&mut players, id, true);
+                    }
+                    EventKind::Keyboard(input)
+                        if input.state == ElementState::Pressed
+                            && input.virtual_keycode == Some(VirtualKeyCode::Back) =>
+                    {
+                        changed |= remove_uncontrolled(&mut players);
+                    }
+                    kind => match input_center.joining(&kind) {
+                        Some(_) if players.len() >= most => {
+                            debug!("No more players at tick {}", event.tick);
                         }
+                        Some(source) => changed |= join(input_center, &mut players, source),
+                        None => {}
                     },
-                )?
-                .unwrap_or(());
+                }
+            }
+            changed |= update_status(&mut players);
+
+            if changed {
+                log_players(&players);
+                if countdown.take().is_some() {
+                    info!("Countdown cancelled");
+                }
+            }
+            let ready = players.len() >= least
+                && players
+                    .iter()
+                    .all(|player| player.status == ControllerStatus::Prepared);
+            countdown = match countdown {
+                _ if !ready => None,
+                Some(0) => return Ok(players),
+                Some(ticks) => Some(ticks - 1),
+                None => Some(countdown_ticks),
+            };
+            if let Some(left) = countdown.filter(|left| *left > 0 && left % ticks_per_second == 0) {
+                info!("The match starts in {}", left / ticks_per_second);
+            }
+
+            ticks += 1;
+            let blink = ticks % BLINK_TICKS == 0;
+            if changed || blink || countdown.is_some() {
+                let progress = countdown.map(|left| 1.0 - left as f32 / countdown_ticks as f32);
+                let shown = ticks / BLINK_TICKS % 2 == 0;
+                let frame = draw(&players, most, progress, shown, &instructions);
+                self.frame_update_sender.send(frame)?;
+            }
+        }
+    }
+}
+
+/// Returns false if the keyboard cluster or the gamepad has joined already.
+fn join(input_center: &InputCenter, players: &mut Vec<Player>, source: Source) -> bool {
+    let bot = matches!(source, Source::Bot(_));
+    if !bot && players.iter().any(|player| player.source == source) {
+        return false;
+    }
+    let controller = input_center.create_controller(source);
+    let taken: Vec<usize> = players.iter().map(|player| player.colour).collect();
+    let name = match source {
+        Source::Keyboard(cluster) => {
+            let cluster = &input_center.key_bindings().clusters[cluster];
+            format!("{} ({})", cluster.name, cluster.fire)
+        }
+        Source::Gamepad(id) => input_center.gamepad_name(id),
+        Source::Bot(difficulty) => format!("{} bot", difficulty),
+    };
+    // The press which joined doesn't make it ready.
+    let last_input = PlayerInput {
+        fire: true,
+        ..controller.input()
+    };
+    players.push(Player {
+        controller,
+        source,
+        name,
+        status: if bot {
+            ControllerStatus::Prepared
+        } else {
+            ControllerStatus::Unprepared
+        },
+        colour: palette::free_colour(&taken, 0),
+        last_input,
+    });
+    true
+}
+
+/// The player of a gamepad waits while it's disconnected, returns true if anyone is found.
+fn connect(players: &mut [Player], id: GamepadId, connected: bool) -> bool {
+    let player = players
+        .iter_mut()
+        .find(|player| player.source == Source::Gamepad(id));
+    match player {
+        Some(player) => {
+            player.status = if connected {
+                ControllerStatus::Unprepared
+            } else {
+                ControllerStatus::Unknown
+            };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Backspace removes the last player no one controls, a bot or a disconnected gamepad.
+fn remove_uncontrolled(players: &mut Vec<Player>) -> bool {
+    let last = players.iter().rposition(|player| {
+        matches!(player.source, Source::Bot(_)) || player.status == ControllerStatus::Unknown
+    });
+    match last {
+        Some(index) => {
+            players.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Firing toggles ready, turning picks the previous or the next colour and pulling back
+/// leaves if not ready. Returns true if anything changes.
+fn update_status(players: &mut Vec<Player>) -> bool {
+    let mut changed = false;
+    let mut index = 0;
+    while index < players.len() {
+        let taken: Vec<usize> = players
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, player)| player.colour)
+            .collect();
+        let player = &mut players[index];
+        if matches!(player.source, Source::Bot(_)) || player.status == ControllerStatus::Unknown {
+            index += 1;
+            continue;
         }
-        Ok(players.take())
+        let input = player.controller.input();
+        let last_input = std::mem::replace(&mut player.last_input, input);
+        let fired = input.fire && !last_input.fire;
+        let backed = input.movement.1 < -0.5 && last_input.movement.1 >= -0.5;
+        let turned = input.movement.0.abs() > 0.5 && last_input.movement.0.abs() <= 0.5;
+        match player.status {
+            ControllerStatus::Unprepared if backed => {
+                players.remove(index);
+                changed = true;
+                continue;
+            }
+            ControllerStatus::Unprepared if turned => {
+                let forward = input.movement.0 > 0.0;
+                player.colour = palette::next_colour(&taken, player.colour, forward);
+                changed = true;
+            }
+            ControllerStatus::Unprepared if fired => {
+                player.status = ControllerStatus::Prepared;
+                changed = true;
+            }
+            ControllerStatus::Prepared if fired => {
+                player.status = ControllerStatus::Unprepared;
+                changed = true;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    changed
+}
+
+fn log_players(players: &[Player]) {
+    let players: Vec<String> = players
+        .iter()
+        .map(|player| {
+            let colour = COLOURS[player.colour].name;
+            format!("{} {} {}", colour, player.name, status_text(player.status))
+        })
+        .collect();
+    info!("Players: {}", players.join(", "));
+}
+
+fn status_text(status: ControllerStatus) -> &'static str {
+    match status {
+        ControllerStatus::Prepared => "ready",
+        ControllerStatus::Unknown => "disconnected",
+        ControllerStatus::Unprepared => "not ready",
     }
 }
 
-impl SceneRender for PrepareSceneRender {
-    fn render(
-        &mut self,
-        _device: &Device,
-        _queue: &Queue,
-        _frame: &SwapChainTexture,
-        _frame_size: [u32; 2],
-    ) -> Result<(), SwapChainError> {
-        Ok(())
+/// Draw a slot for every place, empty slots show a plus. `progress` of the countdown
+/// fills the bar below the slots, disconnected gamepads blink while `shown` changes.
+fn draw(
+    players: &[Player],
+    places: usize,
+    progress: Option<f32>,
+    shown: bool,
+    instructions: &str,
+) -> SlotsFrame {
+    let mut frame = SlotsFrame::default();
+    for slot in 0..places {
+        let center = slots::slot_center(slot, places);
+        match players.get(slot) {
+            Some(player) => draw_player(&mut frame, center, player, shown),
+            None => {
+                frame.shapes.rect(center, [SLOT_SIZE, SLOT_SIZE], BACKGROUND);
+                frame.shapes.frame(center, [SLOT_SIZE, SLOT_SIZE], 0.04, LIGHT);
+                frame.shapes.rect(center, [0.5, 0.1], LIGHT);
+                frame.shapes.rect(center, [0.1, 0.5], LIGHT);
+            }
+        }
     }
+
+    let half_width = VIEW_SIZE[0] as f32 / 2.0;
+    let message = match progress {
+        Some(progress) => {
+            let width = VIEW_SIZE[0] as f32 * progress;
+            frame.shapes.rect([width / 2.0 - half_width, -2.3], [width, 0.1], READY);
+            let seconds = ((1.0 - progress) * COUNTDOWN).ceil();
+            format!("The match starts in {}", seconds)
+        }
+        None => instructions.to_string(),
+    };
+    frame.message(&message);
+    frame
+}
+
+/// The tank in the colour of the player, the controller and its name below it,
+/// and the status at the bottom.
+fn draw_player(frame: &mut SlotsFrame, center: [f32; 2], player: &Player, shown: bool) {
+    let colour = COLOURS[player.colour].rgba();
+    let [x, y] = center;
+    frame.slot(center, colour);
+    frame.tank([x, y + 0.4], colour);
+
+    let shapes = &mut frame.shapes;
+    let icon = [x, y - 0.2];
+    match player.source {
+        Source::Keyboard(_) => {
+            shapes.rect(icon, [0.8, 0.32], DARK);
+            for row in 0..2 {
+                for key in 0..5 {
+                    let key_center = [
+                        icon[0] - 0.3 + key as f32 * 0.15,
+                        icon[1] + 0.06 - row as f32 * 0.12,
+                    ];
+                    shapes.rect(key_center, [0.1, 0.08], LIGHT);
+                }
+            }
+        }
+        Source::Gamepad(_) => {
+            let colour = if player.status == ControllerStatus::Unknown && !shown {
+                LIGHT
+            } else {
+                DARK
+            };
+            shapes.circle([icon[0] - 0.25, icon[1] - 0.04], 0.14, colour);
+            shapes.circle([icon[0] + 0.25, icon[1] - 0.04], 0.14, colour);
+            shapes.rect(icon, [0.5, 0.24], colour);
+            shapes.rect([icon[0] - 0.25, icon[1]], [0.14, 0.04], LIGHT);
+            shapes.rect([icon[0] - 0.25, icon[1]], [0.04, 0.14], LIGHT);
+            shapes.circle([icon[0] + 0.25, icon[1]], 0.04, LIGHT);
+        }
+        Source::Bot(_) => {
+            shapes.rect([icon[0], icon[1] + 0.2], [0.03, 0.12], DARK);
+            shapes.rect(icon, [0.44, 0.3], DARK);
+            shapes.circle([icon[0] - 0.1, icon[1] + 0.03], 0.05, LIGHT);
+            shapes.circle([icon[0] + 0.1, icon[1] + 0.03], 0.05, LIGHT);
+        }
+    }
+    frame.text(&player.name, [x, y - 0.55], 0.14, DARK);
+
+    let status = match player.status {
+        ControllerStatus::Prepared => READY,
+        ControllerStatus::Unknown => ALERT,
+        ControllerStatus::Unprepared => LIGHT,
+    };
+    frame.status(center, status_text(player.status), status);
 }
 
 impl SceneUpdater for PrepareSceneUpdater {
@@ -107,11 +392,137 @@ impl SceneUpdater for PrepareSceneUpdater {
         format: wgpu::TextureFormat,
         input_center: &InputCenter,
     ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
-        let players = self.manage(input_center).unwrap();
-        let (render, updater) = game_scene::new(device, format);
+        let players = match self.manage(input_center) {
+            Ok(players) => players,
+            Err(err) => {
+                error!("{}", err);
+                return None;
+            }
+        };
+        let seed = self.options.seed.unwrap_or_else(rand::random);
+        let arena = self.options.arena.clone();
+        let colliders = self.options.colliders;
+        if let Some(peers) = self.options.peers.clone() {
+            let controller = players.into_iter().next().unwrap().controller;
+            let player = self.options.player;
+            let (render, updater) = peer_scene::new(
+                device, format, peers, player, seed, arena, colliders, controller,
+            );
+            return Some((Box::new(render), Box::new(updater)));
+        }
+        let (render, updater) = game_scene::new(device, format, seed, arena, colliders);
         for p in players {
-            updater.add_player(p.controller);
+            updater.add_player(p.controller, p.colour);
+        }
+        if let Some(path) = self.options.record.clone() {
+            updater.record_to(path);
         }
         Some((Box::new(render), Box::new(updater)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::input::bot_controller::{Bot, Difficulty};
+
+    use super::*;
+
+    struct Fake(Arc<Mutex<PlayerInput>>);
+
+    impl Controller for Fake {
+        fn movement_status(&self) -> (f32, f32) {
+            self.0.lock().unwrap().movement
+        }
+
+        fn fire_status(&self) -> bool {
+            self.0.lock().unwrap().fire
+        }
+    }
+
+    fn player(source: Source, status: ControllerStatus) -> (Player, Arc<Mutex<PlayerInput>>) {
+        let input = Arc::new(Mutex::new(PlayerInput::default()));
+        let player = Player {
+            controller: Box::new(Fake(input.clone())),
+            source,
+            name: String::new(),
+            status,
+            colour: 0,
+            last_input: PlayerInput::default(),
+        };
+        (player, input)
+    }
+
+    #[test]
+    fn fire_toggles_ready_and_back_leaves() {
+        let (first, input) = player(Source::Keyboard(0), ControllerStatus::Unprepared);
+        let mut players = vec![first];
+        input.lock().unwrap().fire = true;
+        assert!(update_status(&mut players));
+        assert!(players[0].status == ControllerStatus::Prepared);
+        // Holding fire changes nothing.
+        assert!(!update_status(&mut players));
+
+        *input.lock().unwrap() = PlayerInput {
+            movement: (0.0, -0.6),
+            fire: false,
+        };
+        // Ready players don't leave.
+        assert!(!update_status(&mut players));
+        input.lock().unwrap().movement = (0.0, 0.0);
+        input.lock().unwrap().fire = true;
+        assert!(update_status(&mut players));
+        assert!(players[0].status == ControllerStatus::Unprepared);
+
+        input.lock().unwrap().movement = (0.0, -0.6);
+        assert!(update_status(&mut players));
+        assert!(players.is_empty());
+    }
+
+    #[test]
+    fn turning_picks_a_free_colour() {
+        let (first, input) = player(Source::Keyboard(0), ControllerStatus::Unprepared);
+        let (mut second, _) = player(Source::Keyboard(1), ControllerStatus::Unprepared);
+        second.colour = 1;
+        let mut players = vec![first, second];
+        input.lock().unwrap().movement = (1.0, 0.0);
+        assert!(update_status(&mut players));
+        assert_eq!(players[0].colour, 2);
+        // Holding the turn changes nothing.
+        assert!(!update_status(&mut players));
+
+        input.lock().unwrap().movement = (0.0, 0.0);
+        update_status(&mut players);
+        input.lock().unwrap().movement = (-1.0, 0.0);
+        assert!(update_status(&mut players));
+        assert_eq!(players[0].colour, 0);
+
+        // Ready players keep their colours.
+        players[0].status = ControllerStatus::Prepared;
+        input.lock().unwrap().movement = (0.0, 0.0);
+        update_status(&mut players);
+        input.lock().unwrap().movement = (1.0, 0.0);
+        assert!(!update_status(&mut players));
+        assert_eq!(players[0].colour, 0);
+    }
+
+    #[test]
+    fn backspace_removes_uncontrolled_players() {
+        let (keyboard, _) = player(Source::Keyboard(0), ControllerStatus::Unprepared);
+        let (disconnected, _) = player(Source::Keyboard(1), ControllerStatus::Unknown);
+        let bot = Player {
+            controller: Box::new(Bot::new(Difficulty::Easy)),
+            source: Source::Bot(Difficulty::Easy),
+            name: String::new(),
+            status: ControllerStatus::Prepared,
+            colour: 1,
+            last_input: PlayerInput::default(),
+        };
+        let mut players = vec![keyboard, bot, disconnected];
+        assert!(remove_uncontrolled(&mut players));
+        assert!(remove_uncontrolled(&mut players));
+        assert!(!remove_uncontrolled(&mut players));
+        assert!(players[0].source == Source::Keyboard(0));
+    }
+}
@@ -19,6 +19,7 @@ pub struct VertexAndInstances {
     pub vertex_num: usize,
     pub instance: wgpu::Buffer,
     pub instance_num: usize,
+    pub instance_capacity: usize,
 }
 
 pub struct VertexAndIndexes {
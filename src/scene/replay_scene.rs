@@ -0,0 +1,258 @@
+//! A scene playing a recorded match, drawn by the render of the game scene.
+//!
+//! Keyboard: `Space` plays or pauses, `Left` `Right` step one tick back or forward while paused,
+//! `Up` `Down` change the speed from 0.25x to 4x, `PageUp` `PageDown` seek 5 seconds,
+//! `Home` goes back to the beginning and `0` to `9` seek to that tenth of the match.
+use std::{error::Error, time};
+
+use crossbeam_channel::tick;
+#[allow(unused_imports)]
+use log::{debug, error, info, log_enabled};
+use winit::event::VirtualKeyCode;
+
+use crate::input::{Controller, input_center::InputCenter};
+
+use super::{
+    game_scene::{
+        self,
+        maze_layer::MazeData,
+        replay::Replay,
+        simulation::{Simulation, SimulationState, Snapshot},
+        FrameData, GameSceneRender, RenderChannels, PHYSICAL_DT,
+    },
+    SceneRender, SceneUpdater,
+};
+
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+/// How many ticks `PageUp` and `PageDown` seek.
+const SEEK_TICKS: usize = (5.0 / PHYSICAL_DT) as usize;
+/// How many ticks between two saved states, seeking back starts from the last one before.
+const KEYFRAME_TICKS: usize = (2.0 / PHYSICAL_DT) as usize;
+
+pub struct ReplaySceneUpdater {
+    replay: Replay,
+    channels: RenderChannels,
+}
+
+/// Where the replay is, and how it is played.
+struct Playback<'a> {
+    replay: &'a Replay,
+    simulation: Simulation,
+    controllers: Vec<Box<dyn Controller>>,
+    /// How many ticks are simulated since the beginning.
+    tick: usize,
+    snapshot: Snapshot,
+    /// The state every `KEYFRAME_TICKS` ticks, as far as the replay has been simulated.
+    keyframes: Vec<SimulationState>,
+    paused: bool,
+    speed: f32,
+    /// Ticks to simulate, `speed` is added every real tick.
+    pending: f32,
+}
+
+pub(crate) fn new(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    replay: Replay,
+) -> (GameSceneRender, ReplaySceneUpdater) {
+    info!(
+        "Playing a replay of {} players, {} ticks, seed {}",
+        replay.players,
+        replay.ticks(),
+        replay.seed
+    );
+    let (render, channels) = game_scene::render(device, format);
+    (render, ReplaySceneUpdater { replay, channels })
+}
+
+impl ReplaySceneUpdater {
+    fn manage(&self, input_center: &InputCenter) -> Result<(), Box<dyn Error>> {
+        let mut playback = Playback::new(&self.replay);
+        let ticker = tick(time::Duration::from_secs_f32(PHYSICAL_DT));
+        // The round whose maze has been sent to the render.
+        let mut round = 0;
+        let mut changed = true;
+
+        loop {
+            for key in input_center.update().pressed_keys() {
+                changed |= playback.handle_key(key);
+            }
+            changed |= playback.advance();
+
+            if playback.snapshot.round != round {
+                round = playback.snapshot.round;
+                let maze = MazeData::from(playback.simulation.maze());
+                self.channels.maze_update_sender.send(maze)?;
+            }
+            // Nothing is sent while paused, so the render doesn't forecast the movement again.
+            // Colours aren't recorded, tanks take the ones of their places.
+            let frame = if changed {
+                changed = false;
+                Some(FrameData::new(&playback.snapshot, &[]))
+            } else {
+                None
+            };
+            if !self.channels.wait_tick(&ticker, frame)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Playback<'_> {
+    fn new(replay: &Replay) -> Playback<'_> {
+        let simulation = replay.simulation();
+        Playback {
+            replay,
+            snapshot: simulation.snapshot(),
+            keyframes: vec![simulation.save()],
+            simulation,
+            controllers: replay.controllers(),
+            tick: 0,
+            paused: false,
+            speed: 1.0,
+            pending: 0.0,
+        }
+    }
+
+    /// Simulate the ticks of a real tick, returns whether the snapshot is changed.
+    fn advance(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+        self.pending += self.speed;
+        let mut changed = false;
+        while self.pending >= 1.0 {
+            self.pending -= 1.0;
+            if !self.step() {
+                info!("The replay is over");
+                self.paused = true;
+                self.pending = 0.0;
+                break;
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Simulate the next recorded tick, returns false at the end of the replay.
+    fn step(&mut self) -> bool {
+        if self.tick >= self.ticks() {
+            return false;
+        }
+        let inputs = self.simulation.poll_inputs(&self.controllers);
+        self.snapshot = self.simulation.step(&inputs);
+        self.tick += 1;
+        if self.tick == self.keyframes.len() * KEYFRAME_TICKS {
+            self.keyframes.push(self.simulation.save());
+        }
+        true
+    }
+
+    fn ticks(&self) -> usize {
+        self.replay.ticks() as usize
+    }
+
+    /// Go to the tick by simulating again, from the last keyframe before it
+    /// if it's in the past or the keyframe is ahead.
+    fn seek(&mut self, target: usize) {
+        let target = target.min(self.ticks());
+        let keyframe = (target / KEYFRAME_TICKS).min(self.keyframes.len() - 1);
+        let keyframe_tick = keyframe * KEYFRAME_TICKS;
+        if target < self.tick || keyframe_tick > self.tick {
+            self.simulation.restore(&self.keyframes[keyframe]);
+            self.controllers = self.replay.controllers_at(keyframe_tick as u32);
+            self.snapshot = self.simulation.snapshot();
+            self.tick = keyframe_tick;
+        }
+        while self.tick < target {
+            self.step();
+        }
+        self.pending = 0.0;
+    }
+
+    /// Returns whether the snapshot is changed.
+    fn handle_key(&mut self, key: VirtualKeyCode) -> bool {
+        let tick = self.tick;
+        match key {
+            VirtualKeyCode::Space => {
+                self.paused = !self.paused;
+                let state = if self.paused { "Paused" } else { "Playing" };
+                info!("{} at {}/{}", state, tick, self.ticks());
+                return false;
+            }
+            VirtualKeyCode::Up | VirtualKeyCode::Down => {
+                self.speed = if key == VirtualKeyCode::Up {
+                    (self.speed * 2.0).min(MAX_SPEED)
+                } else {
+                    (self.speed / 2.0).max(MIN_SPEED)
+                };
+                info!("Playing at {}x", self.speed);
+                return false;
+            }
+            VirtualKeyCode::Right if self.paused => self.seek(tick + 1),
+            VirtualKeyCode::Left if self.paused => self.seek(tick.saturating_sub(1)),
+            VirtualKeyCode::PageUp => self.seek(tick + SEEK_TICKS),
+            VirtualKeyCode::PageDown => self.seek(tick.saturating_sub(SEEK_TICKS)),
+            VirtualKeyCode::Home => self.seek(0),
+            _ => match tenth(key) {
+                Some(tenth) => self.seek(self.ticks() * tenth / 10),
+                None => return false,
+            },
+        }
+        tick != self.tick
+    }
+}
+
+fn tenth(key: VirtualKeyCode) -> Option<usize> {
+    use VirtualKeyCode::*;
+    [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+        .iter()
+        .position(|&k| k == key)
+}
+
+impl SceneUpdater for ReplaySceneUpdater {
+    fn update(
+        &self,
+        _device: &wgpu::Device,
+        _format: wgpu::TextureFormat,
+        input_center: &InputCenter,
+    ) -> Option<(Box<dyn SceneRender + Sync + Send>, Box<dyn SceneUpdater>)> {
+        self.manage(input_center)
+            .unwrap_or_else(|err| error!("{}", err));
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::PlayerInput;
+
+    use super::*;
+
+    #[test]
+    fn seeking_back_plays_the_same_match() {
+        let mut replay = Replay::new(2, 3, None, game_scene::WallColliders::Cuboids);
+        for tick in 0..KEYFRAME_TICKS * 3 {
+            let fire = tick % 40 == 0;
+            let left = PlayerInput { movement: ((tick / 60 % 3) as f32 - 1.0, 1.0), fire };
+            let right = PlayerInput { movement: (0.5, 1.0), fire: !fire };
+            replay.record(&[left, right]);
+        }
+        let mut playback = Playback::new(&replay);
+        let mut snapshots = Vec::new();
+        while playback.step() {
+            snapshots.push(playback.snapshot.clone());
+        }
+        assert_eq!(playback.keyframes.len(), 4);
+
+        for &target in [KEYFRAME_TICKS * 2 + 7, KEYFRAME_TICKS - 1, KEYFRAME_TICKS, 5].iter() {
+            playback.seek(target);
+            assert_eq!(playback.tick, target);
+            assert_eq!(playback.snapshot, snapshots[target - 1]);
+            playback.step();
+            assert_eq!(playback.snapshot, snapshots[target]);
+        }
+    }
+}
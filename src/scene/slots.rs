@@ -0,0 +1,180 @@
+//! Slots of players in four columns, drawn by the prepare scene and the lobby.
+use crossbeam_channel::{Receiver, Sender, unbounded};
+use wgpu::{Device, Queue, SwapChainError, SwapChainTexture};
+
+use super::{
+    game_scene::{
+        camera::Camera,
+        shape_layer::{ShapeData, ShapeLayer},
+        text_layer::{Align, Text, TextLayer},
+    },
+    render_layer::Layer,
+    SceneRender,
+};
+
+/// The area drawn, slots in four columns and a message below them.
+pub const VIEW_SIZE: [usize; 2] = [8, 5];
+pub const SLOT_SIZE: f32 = 1.8;
+
+pub const BACKGROUND: [f32; 4] = [0.92, 0.92, 0.92, 1.0];
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+pub const DARK: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+pub const LIGHT: [f32; 4] = [0.75, 0.75, 0.75, 1.0];
+pub const READY: [f32; 4] = [0.30, 0.69, 0.31, 1.0];
+pub const ALERT: [f32; 4] = [0.90, 0.22, 0.21, 1.0];
+
+/// Draws the latest [`SlotsFrame`] sent by the updater.
+pub struct SlotsRender {
+    clean_color: wgpu::Color,
+    camera: Camera,
+    shape_layer: ShapeLayer,
+    text_layer: TextLayer,
+
+    frame_update_chan: Receiver<SlotsFrame>,
+}
+
+#[derive(Default)]
+pub struct SlotsFrame {
+    pub shapes: ShapeData,
+    pub texts: Vec<Text>,
+}
+
+pub fn render(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> (SlotsRender, Sender<SlotsFrame>) {
+    let clean_color = wgpu::Color {
+        r: 1.0,
+        g: 1.0,
+        b: 1.0,
+        a: 1.0,
+    };
+    let camera = Camera::new(device);
+    let shape_layer = ShapeLayer::new(device, format.into(), &camera.uniform_bind_group_layout);
+    let text_layer = TextLayer::new(device, format, &camera.uniform_bind_group_layout);
+    let (frame_update_sender, frame_update_chan) = unbounded();
+    let render = SlotsRender {
+        clean_color,
+        camera,
+        shape_layer,
+        text_layer,
+        frame_update_chan,
+    };
+    (render, frame_update_sender)
+}
+
+/// The center of the slot, of `places` slots in rows of four.
+pub fn slot_center(slot: usize, places: usize) -> [f32; 2] {
+    let columns = places.min(4);
+    let rows = (places + 3) / 4;
+    let (column, row) = ((slot % 4) as f32, (slot / 4) as f32);
+    [
+        (column - (columns - 1) as f32 / 2.0) * 2.0,
+        ((rows - 1) as f32 / 2.0 - row) * 2.0 + 0.4,
+    ]
+}
+
+impl SlotsFrame {
+    /// The slot of a player, framed in the colour of the player.
+    pub fn slot(&mut self, center: [f32; 2], colour: [f32; 4]) {
+        self.shapes.rect(center, [SLOT_SIZE, SLOT_SIZE], BACKGROUND);
+        self.shapes.frame(center, [SLOT_SIZE, SLOT_SIZE], 0.08, colour);
+    }
+
+    /// A tank facing up, `center` is the center of its body.
+    pub fn tank(&mut self, center: [f32; 2], colour: [f32; 4]) {
+        let [x, y] = center;
+        self.shapes.rect([x - 0.3, y], [0.12, 0.6], DARK);
+        self.shapes.rect([x + 0.3, y], [0.12, 0.6], DARK);
+        self.shapes.rect(center, [0.48, 0.5], colour);
+        self.shapes.circle(center, 0.14, DARK);
+        self.shapes.rect([x, y + 0.2], [0.08, 0.4], DARK);
+    }
+
+    /// The status bar at the bottom of the slot, in `colour` with the text on it.
+    pub fn status(&mut self, center: [f32; 2], status: &str, colour: [f32; 4]) {
+        let [x, y] = center;
+        self.shapes.rect([x, y - 0.74], [1.4, 0.2], colour);
+        self.text(status, [x, y - 0.79], 0.13, if colour == LIGHT { DARK } else { WHITE });
+    }
+
+    /// The message below the slots.
+    pub fn message(&mut self, message: &str) {
+        self.text(message, [0.0, -1.75], 0.2, DARK);
+    }
+
+    /// Centered text.
+    pub fn text(&mut self, content: &str, position: [f32; 2], size: f32, colour: [f32; 4]) {
+        self.texts.push(Text {
+            content: content.to_string(),
+            position,
+            size,
+            colour,
+            align: Align::Center,
+        });
+    }
+}
+
+impl SceneRender for SlotsRender {
+    fn render(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame: &SwapChainTexture,
+        frame_size: [u32; 2],
+    ) -> Result<(), SwapChainError> {
+        // Only the latest state is needed
+        if let Some(frame) = self.frame_update_chan.try_iter().last() {
+            self.shape_layer.update_shapes(device, frame.shapes);
+            self.text_layer.update_texts(device, queue, &frame.texts);
+        }
+        self.camera.update(queue, frame_size, VIEW_SIZE, 0.0);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Slots Render Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Draw player slots"),
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &frame.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clean_color),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_bind_group(0, &self.camera.uniform_bind_group, &[]);
+            self.shape_layer.sub_render_pass(&mut render_pass);
+            self.text_layer.sub_render_pass(&mut render_pass);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_fit_the_view() {
+        for places in 1..=8 {
+            let centers: Vec<[f32; 2]> =
+                (0..places).map(|slot| slot_center(slot, places)).collect();
+            for (i, a) in centers.iter().enumerate() {
+                assert!(a[0].abs() + SLOT_SIZE / 2.0 <= VIEW_SIZE[0] as f32 / 2.0);
+                assert!(a[1].abs() + SLOT_SIZE / 2.0 <= VIEW_SIZE[1] as f32 / 2.0);
+                for b in centers[i + 1..].iter() {
+                    let apart = (a[0] - b[0]).abs().max((a[1] - b[1]).abs());
+                    assert!(apart >= SLOT_SIZE);
+                }
+            }
+        }
+        assert_eq!(slot_center(0, 2), [-1.0, 0.4]);
+    }
+}
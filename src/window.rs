@@ -1,12 +1,18 @@
 use std::{error::Error, sync::Arc, thread};
 
-use crossbeam_channel::{bounded, Receiver, unbounded};
+use crossbeam_channel::{bounded, Receiver, TryRecvError, unbounded};
 #[allow(unused_imports)]
 use log::{debug, error, info, log_enabled};
 use winit::window::Window;
 
-use crate::input::input_center::{InputCenter, InputEventSender};
-use crate::scene::{prepare_scene, SceneRender, SceneUpdater};
+use crate::input::{
+    input_center::{InputCenter, InputEventSender},
+    key_bindings::KeyBindings,
+};
+use crate::options::Options;
+use crate::scene::{
+    editor_scene, lobby_scene, prepare_scene, replay_scene, SceneRender, SceneUpdater,
+};
 
 pub struct WindowState {
     surface: wgpu::Surface,
@@ -22,7 +28,11 @@ pub struct WindowState {
 }
 
 impl WindowState {
-    pub async fn new(window: &Window) -> Result<Self, Box<dyn Error>> {
+    pub async fn new(
+        window: &Window,
+        options: Options,
+        key_bindings: KeyBindings,
+    ) -> Result<Self, Box<dyn Error>> {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
@@ -61,13 +71,33 @@ impl WindowState {
             let format = sc_desc.format;
             thread::spawn(move || {
                 debug!("Update thread start");
-                let (input_center, input_event_sender) = InputCenter::new();
+                let (input_center, input_event_sender) = InputCenter::new(key_bindings);
                 input_event_sender_sender.send(input_event_sender).unwrap();
 
-                let (render, updater) = prepare_scene::new(device.clone(), format);
-                let render: Box<dyn SceneRender + Sync + std::marker::Send> = Box::new(render);
+                let (render, mut updater): (
+                    Box<dyn SceneRender + Sync + std::marker::Send>,
+                    Box<dyn SceneUpdater>,
+                ) = match (options.edit.clone(), options.replay.clone()) {
+                    (Some(path), _) => {
+                        let (render, updater) =
+                            editor_scene::new(device.as_ref(), format, path, options.arena);
+                        (Box::new(render), Box::new(updater))
+                    }
+                    (None, Some(replay)) => {
+                        let (render, updater) = replay_scene::new(device.as_ref(), format, replay);
+                        (Box::new(render), Box::new(updater))
+                    }
+                    (None, None) if options.connect.is_some() || options.browse => {
+                        let (render, updater) =
+                            lobby_scene::new(device.as_ref(), format, options.connect, None);
+                        (Box::new(render), Box::new(updater))
+                    }
+                    (None, None) => {
+                        let (render, updater) = prepare_scene::new(device.as_ref(), format, options);
+                        (Box::new(render), Box::new(updater))
+                    }
+                };
                 update_scene_sender.send(render).unwrap();
-                let mut updater: Box<dyn SceneUpdater> = Box::new(updater);
 
                 while let Some((render_n, updater_n)) =
                 updater.update(device.as_ref(), format, &input_center)
@@ -78,7 +108,8 @@ impl WindowState {
                 debug!("Update thread stop");
             });
         }
-        let input_event_sender = input_event_sender_receiver.recv()?;
+        let mut input_event_sender = input_event_sender_receiver.recv()?;
+        input_event_sender.resize(size);
         let current_scene = update_scene_chan.recv()?;
 
         Ok(Self {
@@ -100,6 +131,7 @@ impl WindowState {
         self.sc_desc.width = new_size.width.max(1);
         self.sc_desc.height = new_size.height.max(1);
         self.swap_chain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+        self.input_event_sender.resize(new_size);
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SwapChainError> {
@@ -110,9 +142,16 @@ impl WindowState {
         Ok(())
     }
 
-    pub fn update(&mut self) {
-        if let Ok(scene) = self.update_scene_chan.try_recv() {
-            self.current_scene = scene;
+    /// Returns false once the update thread has stopped, e.g. a peer of the match left,
+    /// the window would show the last frame forever.
+    pub fn update(&mut self) -> bool {
+        match self.update_scene_chan.try_recv() {
+            Ok(scene) => {
+                self.current_scene = scene;
+                true
+            }
+            Err(TryRecvError::Empty) => true,
+            Err(TryRecvError::Disconnected) => false,
         }
     }
 }